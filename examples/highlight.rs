@@ -30,13 +30,14 @@ use std::io::Read;
 use std::io::stdout;
 
 extern crate rugments;
-use rugments::lexers::html::HtmlLexer;
-use rugments::formatter::{Formatter, HtmlFormatter};
+use rugments::highlight;
+use rugments::lexers::html::HTML_LEXER;
+use rugments::formatters::html::HtmlFormatter;
 
 fn main() {
     let mut bufstr = String::new();
     let filename = env::args().nth(1).unwrap();
     let stdout = stdout();
     File::open(filename).unwrap().read_to_string(&mut bufstr).unwrap();
-    HtmlFormatter::new().format(HtmlLexer::new(&bufstr), stdout.lock()).unwrap();
+    highlight(&bufstr, &HTML_LEXER, &[], &mut HtmlFormatter::new(), stdout.lock()).unwrap();
 }
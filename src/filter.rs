@@ -0,0 +1,87 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Defines the Filter trait.
+
+use lexer::Lexer;
+use token::Token;
+
+/// A boxed stream of tokens, as passed through filters.
+pub type TokenStream<'t> = Box<Iterator<Item=Token<'t>> + 't>;
+
+pub trait Filter {
+    /// Wrap a token stream into one that yields the filtered tokens.
+    ///
+    /// Any options of the filter that the new stream needs have to be
+    /// copied into it, since it may outlive the filter.
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t>;
+}
+
+impl<'a, F: Filter + ?Sized> Filter for &'a F {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        (**self).filter(stream)
+    }
+}
+
+impl<F: Filter + ?Sized> Filter for Box<F> {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        (**self).filter(stream)
+    }
+}
+
+/// A lexer whose tokens have been passed through one or more filters.
+pub struct Filtered<'t> {
+    stream: TokenStream<'t>,
+    source: Option<&'t str>,
+}
+
+impl<'t> Filtered<'t> {
+    pub fn new<F: Filter + ?Sized>(stream: TokenStream<'t>, filter: &F) -> Filtered<'t> {
+        Filtered { stream: filter.filter(stream), source: None }
+    }
+
+    /// Filter the tokens of `lexer`, keeping track of its source text.
+    pub fn from_lexer<L, F>(lexer: L, filter: &F) -> Filtered<'t>
+        where L: Lexer<'t> + 't, F: Filter + ?Sized
+    {
+        let source = lexer.source();
+        Filtered { stream: filter.filter(Box::new(lexer)), source: source }
+    }
+}
+
+impl<'t> Iterator for Filtered<'t> {
+    type Item = Token<'t>;
+
+    fn next(&mut self) -> Option<Token<'t>> {
+        self.stream.next()
+    }
+}
+
+impl<'t> Lexer<'t> for Filtered<'t> {
+    fn source(&self) -> Option<&'t str> {
+        self.source
+    }
+}
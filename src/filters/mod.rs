@@ -0,0 +1,356 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Builtin token filters.
+
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+
+use regex::{self, Regex};
+
+use filter::{Filter, TokenStream};
+use token::{Token, TokenType};
+
+// Take a part of a token text, borrowing from the source if possible.
+fn slice<'t>(text: &Cow<'t, str>, start: usize, end: usize) -> Cow<'t, str> {
+    match *text {
+        Cow::Borrowed(s) => Cow::Borrowed(&s[start..end]),
+        Cow::Owned(ref s) => Cow::Owned(s[start..end].to_owned()),
+    }
+}
+
+// Create a token for a part of another one, at `start` in its text.
+fn part<'t>(tok: &Token<'t>, text: Cow<'t, str>, ttype: TokenType, start: usize) -> Token<'t> {
+    Token { text: text, ttype: ttype, offset: tok.offset.map(|offset| offset + start) }
+}
+
+// Split the token at all matches of `rx`, giving the matches the `special`
+// type and passing them through `replace`.
+fn replace_special<'t, F>(tok: Token<'t>, rx: &Regex, special: TokenType, replace: F)
+                          -> Vec<Token<'t>>
+    where F: Fn(Cow<'t, str>) -> Cow<'t, str>
+{
+    let mut result = Vec::new();
+    let mut last = 0;
+    for (start, end) in rx.find_iter(&tok.text) {
+        if start != last {
+            result.push(part(&tok, slice(&tok.text, last, start), tok.ttype, last));
+        }
+        result.push(part(&tok, replace(slice(&tok.text, start, end)), special, start));
+        last = end;
+    }
+    if last != tok.text.len() {
+        result.push(part(&tok, slice(&tok.text, last, tok.text.len()), tok.ttype, last));
+    }
+    result
+}
+
+/// Letter case for `KeywordCaseFilter`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Case {
+    Lower,
+    Upper,
+    /// First letter uppercase, the rest lowercase.
+    Capitalize,
+}
+
+/// Convert keywords to a given letter case.
+///
+/// Useful for languages with case-insensitive keywords, like Pascal or SQL.
+pub struct KeywordCaseFilter {
+    case: Case,
+}
+
+impl KeywordCaseFilter {
+    pub fn new(case: Case) -> Self {
+        KeywordCaseFilter { case: case }
+    }
+}
+
+impl Filter for KeywordCaseFilter {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        let case = self.case;
+        Box::new(stream.map(move |tok| {
+            if !tok.ttype.is_subtype_of(TokenType::Keyword) {
+                return tok;
+            }
+            let text = match case {
+                Case::Lower => tok.text.to_lowercase(),
+                Case::Upper => tok.text.to_uppercase(),
+                Case::Capitalize => {
+                    let mut chars = tok.text.chars();
+                    match chars.next() {
+                        Some(first) => first.to_uppercase().chain(
+                            chars.as_str().to_lowercase().chars()).collect(),
+                        None => String::new(),
+                    }
+                }
+            };
+            Token { text: text.into(), ..tok }
+        }))
+    }
+}
+
+/// Give a special token type to selected names.
+///
+/// Names are matched exactly; the type defaults to `NameBuiltin`.
+pub struct NameHighlightFilter {
+    names: HashSet<String>,
+    tokentype: TokenType,
+}
+
+impl NameHighlightFilter {
+    pub fn new(names: &[&str]) -> Self {
+        NameHighlightFilter {
+            names: names.iter().map(|&name| name.to_owned()).collect(),
+            tokentype: TokenType::NameBuiltin,
+        }
+    }
+
+    add_option!(tokentype, with_tokentype, TokenType);
+}
+
+impl Filter for NameHighlightFilter {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        let names = self.names.clone();
+        let tokentype = self.tokentype;
+        Box::new(stream.map(move |mut tok| {
+            if tok.ttype.is_subtype_of(TokenType::Name) && names.contains(&*tok.text) {
+                tok.ttype = tokentype;
+            }
+            tok
+        }))
+    }
+}
+
+/// Replace spaces, tabs and newlines with visible characters.
+///
+/// By default spaces are shown as `·`, tabs as `»` padded to the tab size,
+/// and newlines as `¶` (followed by the newline itself).  With `wstokentype`
+/// enabled, which is the default, the replaced characters are split off
+/// into tokens of type `Whitespace`.
+pub struct VisibleWhitespaceFilter {
+    spaces: Option<char>,
+    tabs: Option<char>,
+    newlines: Option<char>,
+    tabsize: usize,
+    wstokentype: bool,
+}
+
+impl VisibleWhitespaceFilter {
+    pub fn new() -> Self {
+        VisibleWhitespaceFilter {
+            spaces: Some('·'),
+            tabs: Some('»'),
+            newlines: Some('¶'),
+            tabsize: 8,
+            wstokentype: true,
+        }
+    }
+
+    /// Set the character for spaces, or `None` to leave them alone.
+    pub fn with_spaces(mut self, value: Option<char>) -> Self {
+        self.spaces = value;
+        self
+    }
+
+    /// Set the character for tabs, or `None` to leave them alone.
+    pub fn with_tabs(mut self, value: Option<char>) -> Self {
+        self.tabs = value;
+        self
+    }
+
+    /// Set the character for newlines, or `None` to leave them alone.
+    pub fn with_newlines(mut self, value: Option<char>) -> Self {
+        self.newlines = value;
+        self
+    }
+
+    add_option!(tabsize,     with_tabsize,     usize);
+    add_option!(wstokentype, with_wstokentype, bool);
+}
+
+impl Filter for VisibleWhitespaceFilter {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        let spaces = self.spaces.map(|ch| ch.to_string());
+        let tabs = self.tabs.map(|ch| {
+            let mut s = ch.to_string();
+            s.extend((1..self.tabsize).map(|_| ' '));
+            s
+        });
+        let newlines = self.newlines.map(|ch| format!("{}\n", ch));
+        let replacement = move |ch: &str| match ch {
+            " " => spaces.clone(),
+            "\t" => tabs.clone(),
+            "\n" => newlines.clone(),
+            _ => None,
+        };
+        if self.wstokentype {
+            let rx = Regex::new(r"\s").unwrap();
+            Box::new(stream.flat_map(move |tok| {
+                replace_special(tok, &rx, TokenType::Whitespace,
+                                |ws| replacement(&ws).map(Cow::Owned).unwrap_or(ws))
+            }))
+        } else {
+            Box::new(stream.map(move |tok| {
+                if !tok.text.contains(|ch| ch == ' ' || ch == '\t' || ch == '\n') {
+                    return tok;
+                }
+                let mut text = String::with_capacity(tok.text.len());
+                for ch in tok.text.chars() {
+                    let mut buf = [0; 4];
+                    let ch = ch.encode_utf8(&mut buf);
+                    text.push_str(replacement(ch).as_ref().map(|s| &**s).unwrap_or(ch));
+                }
+                Token { text: text.into(), ..tok }
+            }))
+        }
+    }
+}
+
+/// Panic when the lexer produces an `Error` token.
+///
+/// Meant for tests and tools that need to reject input a lexer doesn't
+/// fully understand.  Use `RaiseOnErrorTokenFilter::check` to get the error
+/// as a value instead.
+///
+/// # Panics
+///
+/// The filtered stream panics when it reaches an `Error` token, with the
+/// message of the corresponding `ErrorTokenError`.
+pub struct RaiseOnErrorTokenFilter;
+
+impl RaiseOnErrorTokenFilter {
+    /// Pass `tokens` through, yielding an error for the first `Error` token
+    /// and ending after it.
+    pub fn check<'t, I: Iterator<Item=Token<'t>>>(tokens: I) -> CheckedTokens<I> {
+        CheckedTokens { inner: tokens, failed: false }
+    }
+}
+
+impl Filter for RaiseOnErrorTokenFilter {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        Box::new(RaiseOnErrorTokenFilter::check(stream).map(|res| match res {
+            Ok(tok) => tok,
+            Err(err) => panic!("{}", err),
+        }))
+    }
+}
+
+/// Token iterator adapter returned by `RaiseOnErrorTokenFilter::check`.
+pub struct CheckedTokens<I> {
+    inner: I,
+    failed: bool,
+}
+
+impl<'t, I: Iterator<Item=Token<'t>>> Iterator for CheckedTokens<I> {
+    type Item = Result<Token<'t>, ErrorTokenError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        self.inner.next().map(|tok| if tok.ttype == TokenType::Error {
+            self.failed = true;
+            Err(ErrorTokenError { text: tok.text.into_owned(), offset: tok.offset })
+        } else {
+            Ok(tok)
+        })
+    }
+}
+
+/// An `Error` token found by `RaiseOnErrorTokenFilter`.
+#[derive(Debug)]
+pub struct ErrorTokenError {
+    pub text: String,
+    /// Byte offset of the token in the source text, if the lexer knows it.
+    pub offset: Option<usize>,
+}
+
+impl fmt::Display for ErrorTokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "lexer produced an error token: {:?}", self.text));
+        if let Some(offset) = self.offset {
+            try!(write!(f, " at offset {}", offset));
+        }
+        Ok(())
+    }
+}
+
+impl Error for ErrorTokenError {
+    fn description(&self) -> &str {
+        "lexer produced an error token"
+    }
+}
+
+/// Highlight special code tags in comments and docstrings.
+///
+/// The default tags are `XXX`, `TODO`, `FIXME`, `BUG` and `NOTE`; they are
+/// given the type `CommentSpecial`.  With no (non-empty) tags, the filter
+/// passes all tokens through unchanged.
+pub struct CodeTagFilter {
+    tag_re: Option<Regex>,
+}
+
+impl CodeTagFilter {
+    pub fn new() -> Self {
+        CodeTagFilter::with_codetags(&["XXX", "TODO", "FIXME", "BUG", "NOTE"])
+    }
+
+    pub fn with_codetags(codetags: &[&str]) -> Self {
+        let tags = codetags.iter().filter(|tag| !tag.is_empty())
+                                  .map(|tag| regex::quote(tag)).collect::<Vec<_>>();
+        CodeTagFilter {
+            tag_re: if tags.is_empty() {
+                None
+            } else {
+                Some(Regex::new(&format!(r"\b(?:{})\b", tags.join("|"))).unwrap())
+            },
+        }
+    }
+}
+
+impl Filter for CodeTagFilter {
+    fn filter<'t>(&self, stream: TokenStream<'t>) -> TokenStream<'t> {
+        let rx = match self.tag_re {
+            Some(ref rx) => rx.clone(),
+            None => return stream,
+        };
+        Box::new(stream.flat_map(move |tok| {
+            let ttype = tok.ttype;
+            if ttype.is_subtype_of(TokenType::StringDoc) ||
+                (ttype.is_subtype_of(TokenType::Comment) &&
+                 !ttype.is_subtype_of(TokenType::CommentPreproc))
+            {
+                replace_special(tok, &rx, TokenType::CommentSpecial, |tag| tag)
+            } else {
+                vec![tok]
+            }
+        }))
+    }
+}
@@ -46,3 +46,59 @@ pub trait Formatter {
     fn format<'a, I, W>(&mut self, source: I, out: W) -> Result<()>
         where I: Iterator<Item=Token<'a>>, W: Write;
 }
+
+/// Object-safe version of `Formatter`, for selecting formatters at runtime.
+///
+/// It is implemented for all formatters; and `Box<DynFormatter>` implements
+/// `Formatter` again, so a boxed formatter can be used like any other.
+pub trait DynFormatter {
+    /// Same as `Formatter::get_stylesheet`.
+    fn get_stylesheet_dyn(&self, arg: &str) -> String;
+
+    /// Same as `Formatter::format`, with trait objects for the source and
+    /// the writer.
+    fn format_dyn<'a>(&mut self, source: &mut Iterator<Item=Token<'a>>,
+                      out: &mut Write) -> Result<()>;
+}
+
+impl<F: Formatter> DynFormatter for F {
+    fn get_stylesheet_dyn(&self, arg: &str) -> String {
+        self.get_stylesheet(arg)
+    }
+
+    fn format_dyn<'a>(&mut self, source: &mut Iterator<Item=Token<'a>>,
+                      out: &mut Write) -> Result<()> {
+        self.format(source, out)
+    }
+}
+
+impl<'f> Formatter for Box<DynFormatter + 'f> {
+    fn get_stylesheet(&self, arg: &str) -> String {
+        (**self).get_stylesheet_dyn(arg)
+    }
+
+    fn format<'a, I, W>(&mut self, mut source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        (**self).format_dyn(&mut source, &mut out)
+    }
+}
+
+/// Information about a formatter, used to select one at runtime.
+pub struct FormatterInfo {
+    /// Human-readable name of the formatter.
+    pub name: &'static str,
+    /// Short names to select the formatter by.
+    pub aliases: &'static [&'static str],
+    /// Glob patterns for output file names the formatter produces.
+    pub filenames: &'static [&'static str],
+    /// Constructor for the formatter, with default options.
+    pub new: fn() -> Box<DynFormatter>,
+}
+
+impl FormatterInfo {
+    /// Create the formatter with default options.
+    pub fn formatter(&self) -> Box<DynFormatter> {
+        (self.new)()
+    }
+}
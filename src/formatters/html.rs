@@ -24,16 +24,75 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-macro_rules! w { ($out:expr, $expr:expr) => { try!($out.write($expr.as_bytes())) } }
-macro_rules! wf { ($out:expr, $($tt:tt)*) => { try!(write!($out, $($tt)*)) } }
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::{ErrorKind, Result, Write};
+use std::path::Path;
+use std::rc::Rc;
 
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use style::{Style, TokenStyle};
+use token::{Token, TokenType};
+
+/// How `HtmlFormatter` shows line numbers, if they are enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinenosMode {
+    /// Line numbers in a separate table column.
+    Table,
+    /// Line numbers in a `<span class="linenos">` at the start of each line.
+    /// They are excluded from copying by the stylesheet.
+    Inline,
+}
+
+impl Default for LinenosMode {
+    fn default() -> LinenosMode {
+        LinenosMode::Table
+    }
+}
+
+// Inline line number styles for `noclasses` mode, also used in the stylesheet.
+const LINENOS_CSS: &'static str = "padding-right: 5px; user-select: none; \
+                                   -webkit-user-select: none";
+// Extra styles for line numbers selected by `linenospecial`.
+const LINENOS_SPECIAL_CSS: &'static str = "color: #000000; background-color: #ffffc0";
+
+/// Format tokens as HTML, wrapped in a `<pre>` block.
+///
+/// With `linenos`, lines are numbered, in a table column or inline as set
+/// by `linenos_mode`.  They are numbered from `linenostart`; with
+/// `linenostep`, only every Nth number is shown, and with `linenospecial`,
+/// every Nth number gets the class `special`.  If `lineanchors` is not
+/// empty, each line is preceded by an anchor with the id
+/// `{lineanchors}{lineno}` (e.g. `L42`), which the line numbers link to
+/// with `anchorlinenos`.  If `linespans` is not empty, each line is wrapped
+/// in a `<span>` with the id `{linespans}{lineno}`.
+///
+/// With `full`, the output is a complete HTML5 document with the given
+/// `title`.  The stylesheet is embedded in it, unless `cssfile` is given:
+/// then the document links to the stylesheet at that URL, relative to the
+/// document.  Formatting never writes the stylesheet itself; call
+/// `write_cssfile` for that.
 #[derive(Default)]
 pub struct HtmlFormatter {
     pre_class: String,
     pre_styles: String,
     classprefix: String,
     linenos: bool,
+    linenos_mode: LinenosMode,
+    linenostart: usize,
+    linenostep: usize,
+    linenospecial: usize,
+    anchorlinenos: bool,
+    lineanchors: String,
+    linespans: String,
     nowrap: bool,
+    noclasses: bool,
+    full: bool,
+    title: String,
+    cssfile: String,
+    noclobber_cssfile: bool,
+    hl_lines: HashSet<usize>,
+    style: Style,
     style_map: HashMap<TokenType, Rc<String>>,
 }
 
@@ -42,26 +101,117 @@ impl HtmlFormatter {
         HtmlFormatter {
             pre_class: String::from("highlight"),
             linenos: true,
+            linenostart: 1,
+            linenostep: 1,
             .. Default::default()
         }
     }
 
     add_option!(pre_class,   with_pre_class,   String);
     add_option!(pre_styles,  with_pre_styles,  String);
-    add_option!(classprefix, with_classprefix, String);
     add_option!(linenos,     with_linenos,     bool);
+    add_option!(linenos_mode, with_linenos_mode, LinenosMode);
+    add_option!(linenostart, with_linenostart, usize);
+    add_option!(linenostep,  with_linenostep,  usize);
+    add_option!(linenospecial, with_linenospecial, usize);
+    add_option!(anchorlinenos, with_anchorlinenos, bool);
+    add_option!(lineanchors, with_lineanchors, String);
+    add_option!(linespans,   with_linespans,   String);
     add_option!(nowrap,      with_nowrap,      bool);
+    add_option!(full,        with_full,        bool);
+    add_option!(title,       with_title,       String);
+    add_option!(noclobber_cssfile, with_noclobber_cssfile, bool);
+
+    /// Link the `full` document to the stylesheet at `value` instead of
+    /// embedding it.  The file is not written when formatting; the caller
+    /// must call `write_cssfile` with the document's output directory.
+    pub fn with_cssfile(mut self, value: String) -> Self {
+        self.cssfile = value;
+        self
+    }
 
-    fn get_style_class(&mut self, ttype: TokenType) -> Option<Rc<String>> {
-        if ttype.as_short_str() == "" {
-            None
+    // The following options change the token attributes, so they also
+    // clear the cache of them.
+
+    pub fn with_classprefix(mut self, value: String) -> Self {
+        self.classprefix = value;
+        self.style_map.clear();
+        self
+    }
+
+    pub fn with_noclasses(mut self, value: bool) -> Self {
+        self.noclasses = value;
+        self.style_map.clear();
+        self
+    }
+
+    pub fn with_style(mut self, value: Style) -> Self {
+        self.style = value;
+        self.style_map.clear();
+        self
+    }
+
+    /// Set the lines to highlight, counting from 1.  Ranges can be given
+    /// directly, e.g. `with_hl_lines(3..6)`.
+    pub fn with_hl_lines<L: IntoIterator<Item=usize>>(mut self, lines: L) -> Self {
+        self.hl_lines = lines.into_iter().collect();
+        self
+    }
+
+    /// Write the stylesheet to the file that `cssfile` refers to, for an
+    /// HTML document in the directory `outdir`.
+    ///
+    /// With `noclobber_cssfile`, an existing file is left alone.  Returns
+    /// whether the file was written; it isn't if `cssfile` is empty.
+    pub fn write_cssfile<P: AsRef<Path>>(&self, outdir: P) -> Result<bool> {
+        if self.cssfile.is_empty() {
+            return Ok(false);
+        }
+        let mut options = OpenOptions::new();
+        if self.noclobber_cssfile {
+            options.write(true).create_new(true);
         } else {
-            let prefix = &self.classprefix;
-            Some(self.style_map.entry(ttype).or_insert_with(
-                || Rc::new(format!("{}{}", prefix, ttype.as_short_str()))).clone())
+            options.write(true).create(true).truncate(true);
+        }
+        match options.open(outdir.as_ref().join(&self.cssfile)) {
+            Ok(mut file) => {
+                try!(file.write_all(self.get_stylesheet("").as_bytes()));
+                Ok(true)
+            }
+            Err(ref err) if self.noclobber_cssfile && err.kind() == ErrorKind::AlreadyExists =>
+                Ok(false),
+            Err(err) => Err(err),
         }
     }
 
+    fn get_hll_attr(&self) -> String {
+        match (self.noclasses, self.style.highlight_color) {
+            (true, Some(color)) => format!("style=\"background-color: {}\"", color),
+            (true, None) => String::new(),
+            (false, _) => "class=\"hll\"".into(),
+        }
+    }
+
+    /// Return the attribute for the `<span>` around tokens of the given
+    /// type: either `class="..."` or, in `noclasses` mode, an inline
+    /// `style="..."` taken from the style.
+    fn get_span_attr(&mut self, ttype: TokenType) -> Option<Rc<String>> {
+        if let Some(attr) = self.style_map.get(&ttype) {
+            return if attr.is_empty() { None } else { Some(attr.clone()) };
+        }
+        let attr = if self.noclasses {
+            let css = css_for(self.style.style_for(ttype));
+            if css.is_empty() { css } else { format!("style=\"{}\"", css) }
+        } else if ttype.as_short_str() == "" {
+            String::new()
+        } else {
+            format!("class=\"{}{}\"", self.classprefix, ttype.as_short_str())
+        };
+        let attr = Rc::new(attr);
+        self.style_map.insert(ttype, attr.clone());
+        if attr.is_empty() { None } else { Some(attr) }
+    }
+
     fn write_escaped<W: Write>(&self, source: &str, mut out: W) -> Result<()> {
         let mut last = 0;
         for (i, ch) in source.bytes().enumerate() {
@@ -88,25 +238,91 @@ impl HtmlFormatter {
         Ok(())
     }
 
-    fn format_lines<'a, I, W>(&mut self, source: I, mut out: W) -> Result<usize>
+    // Return the line number text for `line`, padded to `width`, or blanks
+    // if the number should not be shown due to `linenostep`.
+    fn lineno_text(&self, line: usize, width: usize) -> String {
+        if self.linenostep <= 1 || line % self.linenostep == 0 {
+            format!("{:1$}", line, width)
+        } else {
+            " ".repeat(width)
+        }
+    }
+
+    fn format_lines<'a, I, W>(&mut self, source: I, out: W) -> Result<usize>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        if self.linenos && self.linenos_mode == LinenosMode::Inline {
+            // the numbers are padded to the same width, so we need to know
+            // the number of lines beforehand
+            let tokens = source.collect::<Vec<_>>();
+            let linecount = tokens.iter().map(|tok| tok.text.matches('\n').count()).sum::<usize>();
+            let width = format!("{}", self.linenostart + linecount).len();
+            self.write_lines(tokens.into_iter(), out, width)
+        } else {
+            self.write_lines(source, out, 0)
+        }
+    }
+
+    // Write the formatted lines, with inline line numbers of the given width
+    // if it is not zero.
+    fn write_lines<'a, I, W>(&mut self, source: I, mut out: W, numwidth: usize) -> Result<usize>
         where I: Iterator<Item=Token<'a>>, W: Write
     {
+        let hll = self.get_hll_attr();
         let mut last_cls = None;
         let mut linecount = 0;
+        // whether the current line is highlighted and its span opened
+        let mut in_hll = false;
+        let mut line_started = false;
         for tok in source {
-            let cls = self.get_style_class(tok.ttype);
+            let cls = self.get_span_attr(tok.ttype);
             if tok.text.is_empty() {
                 continue;
             }
             let parts = tok.text.split('\n').enumerate().collect::<Vec<_>>();
             let lastpart = parts.len() - 1;
             for (i, part) in parts {
+                if i == lastpart && part.is_empty() {
+                    // don't open spans for the next line before it has content
+                    break;
+                }
+                if !line_started {
+                    line_started = true;
+                    let lineno = self.linenostart + linecount;
+                    if !self.linespans.is_empty() {
+                        wf!(out, "<span id=\"{}{}\">", self.linespans, lineno);
+                    }
+                    if !self.lineanchors.is_empty() {
+                        wf!(out, "<a id=\"{}{}\"></a>", self.lineanchors, lineno);
+                    }
+                    if numwidth > 0 {
+                        let special = self.linenospecial > 0 && lineno % self.linenospecial == 0;
+                        let attr = match (self.noclasses, special) {
+                            (true, true) => format!("style=\"{}; {}\"",
+                                                    LINENOS_CSS, LINENOS_SPECIAL_CSS),
+                            (true, false) => format!("style=\"{}\"", LINENOS_CSS),
+                            (false, true) => "class=\"linenos special\"".into(),
+                            (false, false) => "class=\"linenos\"".into(),
+                        };
+                        let mut num = format!("<span {}>{}</span>", attr,
+                                              self.lineno_text(lineno, numwidth));
+                        if self.anchorlinenos {
+                            num = format!("<a href=\"#{}{}\">{}</a>",
+                                          self.lineanchors, lineno, num);
+                        }
+                        w!(out, num);
+                    }
+                    in_hll = !hll.is_empty() && self.hl_lines.contains(&(linecount + 1));
+                    if in_hll {
+                        wf!(out, "<span {}>", hll);
+                    }
+                }
                 if cls != last_cls {
                     if last_cls.is_some() {
                         w!(out, "</span>");
                     }
                     if let Some(ref cls) = cls {
-                        wf!(out, "<span class=\"{}\">", cls);
+                        wf!(out, "<span {}>", cls);
                     }
                 }
                 try!(self.write_escaped(part, &mut out));
@@ -115,11 +331,29 @@ impl HtmlFormatter {
                         w!(out, "</span>");
                     }
                     w!(out, "\n");
+                    if in_hll {
+                        w!(out, "</span>");
+                    }
+                    if !self.linespans.is_empty() {
+                        w!(out, "</span>");
+                    }
                     linecount += 1;
+                    line_started = false;
                     last_cls = None;
                 }
             }
-            last_cls = cls;
+            if !tok.text.ends_with('\n') {
+                last_cls = cls;
+            }
+        }
+        if last_cls.is_some() {
+            w!(out, "</span>");
+        }
+        if line_started && in_hll {
+            w!(out, "</span>");
+        }
+        if line_started && !self.linespans.is_empty() {
+            w!(out, "</span>");
         }
         Ok(linecount)
     }
@@ -130,8 +364,17 @@ impl HtmlFormatter {
         let class = if !self.pre_class.is_empty() {
             format!(" class=\"{}\"", self.pre_class)
         } else { String::new() };
-        let styles = if !self.pre_styles.is_empty() {
-            format!(" style=\"{}\"", self.pre_styles)
+        let mut styles = self.pre_styles.clone();
+        if self.noclasses {
+            if let Some(bg) = self.style.background_color {
+                if !styles.is_empty() {
+                    styles.push_str("; ");
+                }
+                styles.push_str(&format!("background: {}", bg));
+            }
+        }
+        let styles = if !styles.is_empty() {
+            format!(" style=\"{}\"", styles)
         } else { String::new() };
         // the empty span here is to keep leading empty lines from being ignored
         // by HTML parsers
@@ -141,6 +384,29 @@ impl HtmlFormatter {
         Ok(linecount)
     }
 
+    fn write_doc_header<W: Write>(&self, mut out: W) -> Result<()> {
+        w!(out, "<!DOCTYPE html>\n<html>\n<head>\n  <meta charset=\"utf-8\">\n");
+        w!(out, "  <title>");
+        try!(self.write_escaped(&self.title, &mut out));
+        w!(out, "</title>\n");
+        if !self.noclasses {
+            if self.cssfile.is_empty() {
+                wf!(out, "  <style>\n{}  </style>\n", self.get_stylesheet(""));
+            } else {
+                w!(out, "  <link rel=\"stylesheet\" href=\"");
+                try!(self.write_escaped(&self.cssfile, &mut out));
+                w!(out, "\">\n");
+            }
+        }
+        w!(out, "</head>\n<body>\n");
+        if !self.title.is_empty() {
+            w!(out, "<h2>");
+            try!(self.write_escaped(&self.title, &mut out));
+            w!(out, "</h2>\n");
+        }
+        Ok(())
+    }
+
     fn wrap_linenos<'a, I, W>(&mut self, source: I, mut out: W) -> Result<usize>
         where I: Iterator<Item=Token<'a>>, W: Write
     {
@@ -150,39 +416,114 @@ impl HtmlFormatter {
             format!(" class=\"{}table\"", self.pre_class)
         } else { String::new() };
         wf!(out, "<table{}><tr><td class=\"linenos\"><pre>", class);
-        let maxlen = format!("{}", linecount + 1).len();
-        for line in 1..linecount + 1 {
-            wf!(out, "{:1$}\n", line, maxlen);
+        let start = self.linenostart;
+        let maxlen = format!("{}", start + linecount).len();
+        for line in start..start + linecount {
+            let mut num = self.lineno_text(line, maxlen);
+            if self.linenospecial > 0 && line % self.linenospecial == 0 {
+                num = if self.noclasses {
+                    format!("<span style=\"{}\">{}</span>", LINENOS_SPECIAL_CSS, num)
+                } else {
+                    format!("<span class=\"special\">{}</span>", num)
+                };
+            }
+            if self.anchorlinenos {
+                num = format!("<a href=\"#{}{}\">{}</a>", self.lineanchors, line, num);
+            }
+            wf!(out, "{}\n", num);
         }
         wf!(out, "</pre>\n</td><td class=\"code\">");
-        try!(out.write(&buffer));
+        try!(out.write_all(&buffer));
         wf!(out, "</td></tr></table>\n");
         Ok(linecount)
     }
 }
 
+pub static HTML_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "HTML",
+    aliases: &["html"],
+    filenames: &["*.html", "*.htm"],
+    new: new_html_formatter,
+};
+
+fn new_html_formatter() -> Box<DynFormatter> {
+    Box::new(HtmlFormatter::new())
+}
+
 impl Formatter for HtmlFormatter {
-    fn format<'a, I, W>(&mut self, source: I, out: W) -> Result<()>
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
         where I: Iterator<Item=Token<'a>>, W: Write
     {
+        if self.full {
+            try!(self.write_doc_header(&mut out));
+        }
         if self.nowrap {
-            try!(self.format_lines(source, out));
-        } else if self.linenos {
-            try!(self.wrap_linenos(source, out));
+            try!(self.format_lines(source, &mut out));
+        } else if self.linenos && self.linenos_mode == LinenosMode::Table {
+            try!(self.wrap_linenos(source, &mut out));
         } else {
-            try!(self.wrap_pre(source, out));
+            try!(self.wrap_pre(source, &mut out));
+        }
+        if self.full {
+            w!(out, "</body>\n</html>\n");
         }
         Ok(())
     }
 
-    fn get_stylesheet(&self, mut arg: &str) -> String {
+    /// Return CSS rules for the token classes, each selector prefixed with
+    /// `arg`, or with `pre.<pre_class>` if `arg` is empty.
+    fn get_stylesheet(&self, arg: &str) -> String {
         let prefix = if arg.is_empty() {
             format!("pre.{}", self.pre_class)
         } else {
             arg.to_owned()
         };
         let mut result = String::new();
-        // TODO
+        if let Some(bg) = self.style.background_color {
+            result.push_str(&format!("{} {{ background: {}; }}\n", prefix, bg));
+        }
+        if let Some(hl) = self.style.highlight_color {
+            result.push_str(&format!("{} .hll {{ background-color: {} }}\n", prefix, hl));
+        }
+        result.push_str(&format!("{} .linenos {{ {} }}\n", prefix, LINENOS_CSS));
+        result.push_str(&format!("{} .linenos.special {{ {} }}\n", prefix, LINENOS_SPECIAL_CSS));
+        // the table column is outside of the `<pre>`
+        result.push_str(&format!("td.linenos .special {{ {} }}\n", LINENOS_SPECIAL_CSS));
+        // sort parents before children, which is needed for the CSS cascade
+        let mut styles = self.style.iter().collect::<Vec<_>>();
+        styles.sort_by_key(|&(ttype, _)| (ttype.ancestors().count(), ttype.as_str()));
+        for (ttype, style) in styles {
+            let css = css_for(style);
+            if ttype.as_short_str().is_empty() || css.is_empty() {
+                continue;
+            }
+            result.push_str(&format!("{} .{}{} {{ {} }} /* {} */\n", prefix, self.classprefix,
+                                     ttype.as_short_str(), css, ttype.as_str()));
+        }
         result
     }
 }
+
+// Return the CSS declarations for a token style.
+fn css_for(style: &TokenStyle) -> String {
+    let mut css = Vec::new();
+    if let Some(color) = style.color {
+        css.push(format!("color: {}", color));
+    }
+    if style.bold {
+        css.push("font-weight: bold".into());
+    }
+    if style.italic {
+        css.push("font-style: italic".into());
+    }
+    if style.underline {
+        css.push("text-decoration: underline".into());
+    }
+    if let Some(color) = style.bgcolor {
+        css.push(format!("background-color: {}", color));
+    }
+    if let Some(color) = style.border {
+        css.push(format!("border: 1px solid {}", color));
+    }
+    css.join("; ")
+}
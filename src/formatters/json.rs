@@ -0,0 +1,116 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatter for a JSON token stream.
+
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use token::Token;
+
+/// Escape `text` for use in a JSON string.
+///
+/// Besides the characters JSON requires to be escaped, U+2028 and U+2029
+/// are escaped so that the output can be embedded in JavaScript code.
+pub fn escape_json(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            '\u{8}' => result.push_str("\\b"),
+            '\u{c}' => result.push_str("\\f"),
+            _ if ch < ' ' || ch == '\u{2028}' || ch == '\u{2029}' => {
+                result.push_str(&format!("\\u{:04x}", ch as u32));
+            }
+            _ => result.push(ch),
+        }
+    }
+    result
+}
+
+/// Format tokens as a JSON array.
+///
+/// The output is a single array with one object per token, in source
+/// order, like
+///
+/// ```text
+/// [{"type":"Name.Tag","text":"div","offset":12}]
+/// ```
+///
+/// * `type` is the dotted token type name, as returned by
+///   `TokenType::as_str`.
+/// * `text` is the token text.
+/// * `offset` is the byte offset of the token in the UTF-8 source, as
+///   recorded by the lexer.  Tokens without a recorded offset are taken to
+///   follow directly after the previous token, like in `Lexer::indexed`.
+///
+/// Keys always appear in this order, with no whitespace between tokens.
+/// New keys may be added in later versions; consumers should ignore keys
+/// they don't know.
+pub struct JsonFormatter;
+
+impl JsonFormatter {
+    pub fn new() -> Self {
+        JsonFormatter
+    }
+}
+
+pub static JSON_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "JSON tokens",
+    aliases: &["json"],
+    filenames: &["*.json"],
+    new: new_json_formatter,
+};
+
+fn new_json_formatter() -> Box<DynFormatter> {
+    Box::new(JsonFormatter::new())
+}
+
+impl Formatter for JsonFormatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        let mut offset = 0;
+        let mut first = true;
+        w!(out, "[");
+        for tok in source {
+            if !first {
+                w!(out, ",");
+            }
+            first = false;
+            let tok_offset = tok.offset.unwrap_or(offset);
+            wf!(out, "{{\"type\":\"{}\",\"text\":\"{}\",\"offset\":{}}}",
+                escape_json(tok.ttype.as_str()), escape_json(&tok.text), tok_offset);
+            offset = tok_offset + tok.text.len();
+        }
+        w!(out, "]");
+        Ok(())
+    }
+}
@@ -0,0 +1,268 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatter for LaTeX documents, using the `fancyvrb` package.
+
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use style::{Color, Style};
+use token::{Token, TokenType};
+
+const STYLE_TEMPLATE: &'static str = r#"\makeatletter
+\def\PY@reset{\let\PY@it=\relax \let\PY@bf=\relax%
+    \let\PY@ul=\relax \let\PY@tc=\relax%
+    \let\PY@bc=\relax \let\PY@ff=\relax}
+\def\PY@tok#1{\csname PY@tok@#1\endcsname}
+\def\PY@toks#1+{\ifx\relax#1\empty\else%
+    \PY@tok{#1}\expandafter\PY@toks\fi}
+\def\PY@do#1{\PY@bc{\PY@tc{\PY@ul{%
+    \PY@it{\PY@bf{\PY@ff{#1}}}}}}}
+\def\PY#1#2{\PY@reset\PY@toks#1+\relax+\PY@do{#2}}
+
+%(styles)
+
+\def\PYZbs{\char`\\}
+\def\PYZus{\char`\_}
+\def\PYZob{\char`\{}
+\def\PYZcb{\char`\}}
+\def\PYZca{\char`\^}
+\def\PYZam{\char`\&}
+\def\PYZlt{\char`\<}
+\def\PYZgt{\char`\>}
+\def\PYZsh{\char`\#}
+\def\PYZpc{\char`\%}
+\def\PYZdl{\char`\$}
+\def\PYZhy{\char`\-}
+\def\PYZsq{\char`\'}
+\def\PYZdq{\char`\"}
+\def\PYZti{\char`\~}
+\makeatother
+"#;
+
+/// Escape TeX special characters in `text` with the macros defined by the
+/// style sheet.
+pub fn escape_tex(text: &str, commandprefix: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        let name = match ch {
+            '\\' => "Zbs",
+            '{' => "Zob",
+            '}' => "Zcb",
+            '^' => "Zca",
+            '_' => "Zus",
+            '&' => "Zam",
+            '<' => "Zlt",
+            '>' => "Zgt",
+            '#' => "Zsh",
+            '%' => "Zpc",
+            '$' => "Zdl",
+            '-' => "Zhy",
+            '\'' => "Zsq",
+            '"' => "Zdq",
+            '~' => "Zti",
+            _ => {
+                result.push(ch);
+                continue;
+            }
+        };
+        result.push('\\');
+        result.push_str(commandprefix);
+        result.push_str(name);
+        result.push_str("{}");
+    }
+    result
+}
+
+// Format a color as the "r,g,b" fractions that LaTeX's `[rgb]` model uses.
+fn rgbcolor(color: Option<Color>) -> String {
+    match color {
+        Some(Color(r, g, b)) => format!("{:.2},{:.2},{:.2}", r as f32 / 255.0,
+                                        g as f32 / 255.0, b as f32 / 255.0),
+        None => "1,1,1".into(),
+    }
+}
+
+/// Format tokens as LaTeX code in a `Verbatim` environment.
+///
+/// The output needs the `fancyvrb` and `color` packages and the macros
+/// returned by `get_stylesheet`.  Each token is written as
+/// `\PY{k+kd}{text}`, where `k+kd` are the short names of the token type
+/// and its ancestors.
+pub struct LatexFormatter {
+    style: Style,
+    commandprefix: String,
+    envname: String,
+    verboptions: String,
+    linenos: bool,
+    linenostart: usize,
+    linenostep: usize,
+    name_map: HashMap<TokenType, String>,
+}
+
+impl LatexFormatter {
+    pub fn new() -> Self {
+        LatexFormatter {
+            style: Style::default(),
+            commandprefix: "PY".into(),
+            envname: "Verbatim".into(),
+            verboptions: String::new(),
+            linenos: false,
+            linenostart: 1,
+            linenostep: 1,
+            name_map: HashMap::new(),
+        }
+    }
+
+    add_option!(style,         with_style,         Style);
+    add_option!(commandprefix, with_commandprefix, String);
+    add_option!(envname,       with_envname,       String);
+    add_option!(verboptions,   with_verboptions,   String);
+    add_option!(linenos,       with_linenos,       bool);
+    add_option!(linenostart,   with_linenostart,   usize);
+    add_option!(linenostep,    with_linenostep,    usize);
+}
+
+pub static LATEX_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "LaTeX",
+    aliases: &["latex", "tex"],
+    filenames: &["*.tex"],
+    new: new_latex_formatter,
+};
+
+fn new_latex_formatter() -> Box<DynFormatter> {
+    Box::new(LatexFormatter::new())
+}
+
+impl Formatter for LatexFormatter {
+    /// Return the macro definitions for the style.
+    ///
+    /// The argument is ignored.
+    fn get_stylesheet(&self, _arg: &str) -> String {
+        let cp = &self.commandprefix;
+        let mut styles = self.style.iter().collect::<Vec<_>>();
+        styles.sort_by_key(|&(ttype, _)| (ttype.ancestors().count(), ttype.as_str()));
+        let mut defs = Vec::new();
+        for (ttype, style) in styles {
+            let mut def = String::new();
+            if style.bold {
+                def.push_str(r"\let\$$@bf=\textbf");
+            }
+            if style.italic {
+                def.push_str(r"\let\$$@it=\textit");
+            }
+            if style.underline {
+                def.push_str(r"\let\$$@ul=\underline");
+            }
+            if style.roman {
+                def.push_str(r"\let\$$@ff=\textrm");
+            }
+            if style.sans {
+                def.push_str(r"\let\$$@ff=\textsf");
+            }
+            if style.mono {
+                def.push_str(r"\let\$$@ff=\texttt");
+            }
+            if style.color.is_some() {
+                def.push_str(&format!(r"\def\$$@tc##1{{\textcolor[rgb]{{{}}}{{##1}}}}",
+                                      rgbcolor(style.color)));
+            }
+            if style.border.is_some() {
+                def.push_str(&format!(concat!(r"\def\$$@bc##1{{{{\setlength{{\fboxsep}}",
+                                              r"{{\string -\fboxrule}}\fcolorbox[rgb]{{{}}}{{{}}}",
+                                              r"{{\strut ##1}}}}}}"),
+                                      rgbcolor(style.border), rgbcolor(style.bgcolor)));
+            } else if style.bgcolor.is_some() {
+                def.push_str(&format!(concat!(r"\def\$$@bc##1{{{{\setlength{{\fboxsep}}{{0pt}}",
+                                              r"\colorbox[rgb]{{{}}}{{\strut ##1}}}}}}"),
+                                      rgbcolor(style.bgcolor)));
+            }
+            if def.is_empty() || ttype.as_short_str().is_empty() {
+                continue;
+            }
+            defs.push(format!(r"\@namedef{{{}@tok@{}}}{{{}}}", cp, ttype.as_short_str(),
+                              def.replace("$$", cp)));
+        }
+        STYLE_TEMPLATE.replace(r"\PY", &format!(r"\{}", cp))
+                      .replace("PY@tok@#1", &format!("{}@tok@#1", cp))
+                      .replace("%(styles)", &defs.join("\n"))
+    }
+
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        wf!(out, r"\begin{{{}}}[commandchars=\\\{{\}}", self.envname);
+        if self.linenos {
+            w!(out, ",numbers=left");
+            if self.linenostart != 1 {
+                wf!(out, ",firstnumber={}", self.linenostart);
+            }
+            if self.linenostep != 1 {
+                wf!(out, ",stepnumber={}", self.linenostep);
+            }
+        }
+        if !self.verboptions.is_empty() {
+            wf!(out, ",{}", self.verboptions);
+        }
+        w!(out, "]\n");
+        let mut at_line_start = true;
+        for tok in source {
+            if tok.text.is_empty() {
+                continue;
+            }
+            let value = escape_tex(&tok.text, &self.commandprefix);
+            let styleval = self.name_map.entry(tok.ttype).or_insert_with(|| {
+                let mut names = tok.ttype.ancestors().map(|t| t.as_short_str())
+                                                     .filter(|name| !name.is_empty())
+                                                     .collect::<Vec<_>>();
+                names.reverse();
+                names.join("+")
+            });
+            if styleval.is_empty() {
+                w!(out, value);
+            } else {
+                let parts = value.split('\n').collect::<Vec<_>>();
+                let lastpart = parts.len() - 1;
+                for (i, part) in parts.into_iter().enumerate() {
+                    if !part.is_empty() {
+                        wf!(out, r"\{}{{{}}}{{{}}}", self.commandprefix, styleval, part);
+                    }
+                    if i != lastpart {
+                        w!(out, "\n");
+                    }
+                }
+            }
+            at_line_start = value.ends_with('\n');
+        }
+        // the end of the environment must be on a line of its own
+        if !at_line_start {
+            w!(out, "\n");
+        }
+        wf!(out, "\\end{{{}}}\n", self.envname);
+        Ok(())
+    }
+}
@@ -24,4 +24,59 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+//! Builtin formatters, and functions to look them up.
+
+use std::path::Path;
+
+use formatter::{DynFormatter, FormatterInfo};
+use util::fnmatch;
+
+macro_rules! w { ($out:expr, $expr:expr) => { try!($out.write_all($expr.as_bytes())) } }
+macro_rules! wf { ($out:expr, $($tt:tt)*) => { try!(write!($out, $($tt)*)) } }
+
 pub mod html;
+pub mod json;
+pub mod latex;
+pub mod other;
+pub mod rtf;
+pub mod svg;
+pub mod terminal;
+pub mod terminal256;
+
+/// All builtin formatters.
+pub static FORMATTERS: &'static [&'static FormatterInfo] = &[
+    &html::HTML_FORMATTER,
+    &json::JSON_FORMATTER,
+    &latex::LATEX_FORMATTER,
+    &other::RAW_TOKEN_FORMATTER,
+    &rtf::RTF_FORMATTER,
+    &svg::SVG_FORMATTER,
+    &terminal::TERMINAL_FORMATTER,
+    &terminal256::TERMINAL256_FORMATTER,
+    &terminal256::TERMINAL_TRUE_COLOR_FORMATTER,
+];
+
+/// Find a formatter by one of its aliases.
+pub fn find_formatter_by_name(alias: &str) -> Option<&'static FormatterInfo> {
+    let alias = alias.to_lowercase();
+    FORMATTERS.iter().cloned().find(|info| info.aliases.contains(&&*alias))
+}
+
+/// Find a formatter whose file name patterns match the given output file name.
+///
+/// Only the final component of the path is matched.
+pub fn find_formatter_for_filename(filename: &str) -> Option<&'static FormatterInfo> {
+    let basename = Path::new(filename).file_name().and_then(|s| s.to_str()).unwrap_or("");
+    FORMATTERS.iter().cloned()
+        .find(|info| info.filenames.iter().any(|p| fnmatch(p, basename)))
+}
+
+/// Create a formatter with default options, selected by one of its aliases.
+pub fn get_formatter_by_name(alias: &str) -> Option<Box<DynFormatter>> {
+    find_formatter_by_name(alias).map(|info| info.formatter())
+}
+
+/// Create a formatter with default options, selected by the output file name.
+pub fn get_formatter_for_filename(filename: &str) -> Option<Box<DynFormatter>> {
+    find_formatter_for_filename(filename).map(|info| info.formatter())
+}
@@ -0,0 +1,102 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Other formatters.
+
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use token::Token;
+
+// Quote a string like Python's `repr()` does.
+fn python_repr(text: &str) -> String {
+    let quote = if text.contains('\'') && !text.contains('"') { '"' } else { '\'' };
+    let mut result = String::with_capacity(text.len() + 2);
+    result.push(quote);
+    for ch in text.chars() {
+        match ch {
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\t' => result.push_str("\\t"),
+            _ if ch == quote => {
+                result.push('\\');
+                result.push(ch);
+            }
+            // Python escapes all non-printable characters, which includes
+            // whitespace except for the ASCII space
+            _ if ch.is_control() || (ch.is_whitespace() && ch != ' ') => {
+                let code = ch as u32;
+                if code < 0x100 {
+                    result.push_str(&format!("\\x{:02x}", code));
+                } else if code < 0x10000 {
+                    result.push_str(&format!("\\u{:04x}", code));
+                } else {
+                    result.push_str(&format!("\\U{:08x}", code));
+                }
+            }
+            _ => result.push(ch),
+        }
+    }
+    result.push(quote);
+    result
+}
+
+/// Format tokens as a raw dump, one token per line.
+///
+/// Each line has the Pygments name of the token type, a tab and the token
+/// text as a Python string literal, e.g. `Token.Name.Tag\t'div'`.  This is
+/// the format of Pygments' `RawTokenFormatter`; `RawTokenLexer` reads it
+/// back.
+pub struct RawTokenFormatter;
+
+impl RawTokenFormatter {
+    pub fn new() -> Self {
+        RawTokenFormatter
+    }
+}
+
+pub static RAW_TOKEN_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "Raw tokens",
+    aliases: &["raw", "tokens"],
+    filenames: &["*.raw"],
+    new: new_raw_token_formatter,
+};
+
+fn new_raw_token_formatter() -> Box<DynFormatter> {
+    Box::new(RawTokenFormatter::new())
+}
+
+impl Formatter for RawTokenFormatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        for tok in source {
+            wf!(out, "{}\t{}\n", tok.ttype.as_pygments_str(), python_repr(&tok.text));
+        }
+        Ok(())
+    }
+}
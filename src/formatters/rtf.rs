@@ -0,0 +1,159 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatter for RTF documents, which word processors can paste.
+
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use style::{Color, Style};
+use token::Token;
+
+// Escape RTF control characters and non-ASCII characters in `text`.
+//
+// Non-ASCII characters are written as `\uN?`, where N is the signed 16-bit
+// UTF-16 code unit and `?` is the fallback for readers without Unicode
+// support; characters outside the BMP become surrogate pairs.
+fn escape_rtf(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for ch in text.chars() {
+        match ch {
+            '\\' | '{' | '}' => {
+                result.push('\\');
+                result.push(ch);
+            }
+            '\n' => result.push_str("\\par\n"),
+            _ if (ch as u32) < 0x80 => result.push(ch),
+            _ => {
+                let mut buf = [0; 2];
+                for unit in ch.encode_utf16(&mut buf).iter() {
+                    result.push_str(&format!("\\u{}?", *unit as i16));
+                }
+            }
+        }
+    }
+    result
+}
+
+/// Format tokens as an RTF document.
+///
+/// The color table is built from the style; the font is a monospace font,
+/// `fontface` if given, and `fontsize` is in half-points (0 keeps the
+/// default size).
+pub struct RtfFormatter {
+    style: Style,
+    fontface: String,
+    fontsize: usize,
+}
+
+impl RtfFormatter {
+    pub fn new() -> Self {
+        RtfFormatter {
+            style: Style::default(),
+            fontface: String::new(),
+            fontsize: 0,
+        }
+    }
+
+    add_option!(style,    with_style,    Style);
+    add_option!(fontface, with_fontface, String);
+    add_option!(fontsize, with_fontsize, usize);
+}
+
+pub static RTF_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "RTF",
+    aliases: &["rtf"],
+    filenames: &["*.rtf"],
+    new: new_rtf_formatter,
+};
+
+fn new_rtf_formatter() -> Box<DynFormatter> {
+    Box::new(RtfFormatter::new())
+}
+
+impl Formatter for RtfFormatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        wf!(out, "{{\\rtf1\\ansi\\uc1\\deff0{{\\fonttbl{{\\f0\\fmodern\\fprq1\\fcharset0{};}}}}",
+            if self.fontface.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", escape_rtf(&self.fontface))
+            });
+        // color indices start at 1, 0 is the default color
+        w!(out, "{\\colortbl;");
+        let mut styles = self.style.iter().collect::<Vec<_>>();
+        styles.sort_by_key(|&(ttype, _)| (ttype.ancestors().count(), ttype.as_str()));
+        let mut color_map = HashMap::new();
+        for (_, style) in styles {
+            for color in &[style.color, style.bgcolor, style.border] {
+                if let Some(Color(r, g, b)) = *color {
+                    if !color_map.contains_key(&Color(r, g, b)) {
+                        let index = color_map.len() + 1;
+                        color_map.insert(Color(r, g, b), index);
+                        wf!(out, "\\red{}\\green{}\\blue{};", r, g, b);
+                    }
+                }
+            }
+        }
+        w!(out, "}\\f0 ");
+        if self.fontsize != 0 {
+            wf!(out, "\\fs{} ", self.fontsize);
+        }
+        let color_index = |color: Option<Color>| color.and_then(|c| color_map.get(&c));
+        for tok in source {
+            let style = self.style.style_for(tok.ttype);
+            let mut start = String::new();
+            if let Some(index) = color_index(style.bgcolor) {
+                start.push_str(&format!("\\cb{}", index));
+            }
+            if let Some(index) = color_index(style.color) {
+                start.push_str(&format!("\\cf{}", index));
+            }
+            if style.bold {
+                start.push_str("\\b");
+            }
+            if style.italic {
+                start.push_str("\\i");
+            }
+            if style.underline {
+                start.push_str("\\ul");
+            }
+            if let Some(index) = color_index(style.border) {
+                start.push_str(&format!("\\chbrdr\\chcfpat{}", index));
+            }
+            if start.is_empty() {
+                w!(out, escape_rtf(&tok.text));
+            } else {
+                wf!(out, "{{{} {}}}", start, escape_rtf(&tok.text));
+            }
+        }
+        w!(out, "}");
+        Ok(())
+    }
+}
@@ -0,0 +1,231 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatter for SVG images.
+
+use std::cmp;
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use style::Style;
+use token::{Token, TokenType};
+
+/// Format tokens as an SVG image, with one `<text>` element per line.
+///
+/// Lines are placed at absolute positions: the first baseline is at
+/// `xoffset`/`yoffset`, and each further line is `ystep` pixels lower.
+/// The y offset defaults to the font size, the line height to the font
+/// size plus 5.  With `linenos`, numbers are right-aligned in a gutter of
+/// `linenowidth` pixels, and only shown for lines that are a multiple of
+/// `linenostep` (a step of 0 counts as 1).
+///
+/// Unless `nowrap` is set, the root element gets a `width`, `height` and
+/// `viewBox` that fit the text, assuming that each character is 0.6 times
+/// the font size wide, as is typical for monospace fonts.
+pub struct SvgFormatter {
+    style: Style,
+    nowrap: bool,
+    fontfamily: String,
+    fontsize: usize,
+    xoffset: usize,
+    yoffset: Option<usize>,
+    ystep: Option<usize>,
+    spacehack: bool,
+    linenos: bool,
+    linenostart: usize,
+    linenostep: usize,
+    linenowidth: Option<usize>,
+    style_map: HashMap<TokenType, String>,
+}
+
+impl SvgFormatter {
+    pub fn new() -> Self {
+        SvgFormatter {
+            style: Style::default(),
+            nowrap: false,
+            fontfamily: "monospace".into(),
+            fontsize: 14,
+            xoffset: 0,
+            yoffset: None,
+            ystep: None,
+            spacehack: true,
+            linenos: false,
+            linenostart: 1,
+            linenostep: 1,
+            linenowidth: None,
+            style_map: HashMap::new(),
+        }
+    }
+
+    add_option!(style,       with_style,       Style);
+    add_option!(nowrap,      with_nowrap,      bool);
+    add_option!(fontfamily,  with_fontfamily,  String);
+    add_option!(fontsize,    with_fontsize,    usize);
+    add_option!(xoffset,     with_xoffset,     usize);
+    add_option!(spacehack,   with_spacehack,   bool);
+    add_option!(linenos,     with_linenos,     bool);
+    add_option!(linenostart, with_linenostart, usize);
+    add_option!(linenostep,  with_linenostep,  usize);
+
+    /// Set the y position of the first baseline.
+    pub fn with_yoffset(mut self, value: usize) -> Self {
+        self.yoffset = Some(value);
+        self
+    }
+
+    /// Set the line height.
+    pub fn with_ystep(mut self, value: usize) -> Self {
+        self.ystep = Some(value);
+        self
+    }
+
+    /// Set the width of the line number gutter; the default is three times
+    /// the line height.
+    pub fn with_linenowidth(mut self, value: usize) -> Self {
+        self.linenowidth = Some(value);
+        self
+    }
+
+    fn get_style(&mut self, ttype: TokenType) -> &str {
+        let style = &self.style;
+        self.style_map.entry(ttype).or_insert_with(|| {
+            let style = style.style_for(ttype);
+            let mut attrs = String::new();
+            if let Some(color) = style.color {
+                attrs.push_str(&format!(" fill=\"{}\"", color));
+            }
+            if style.bold {
+                attrs.push_str(" font-weight=\"bold\"");
+            }
+            if style.italic {
+                attrs.push_str(" font-style=\"italic\"");
+            }
+            attrs
+        })
+    }
+}
+
+// Escape XML special characters.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+        .replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+pub static SVG_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "SVG",
+    aliases: &["svg"],
+    filenames: &["*.svg"],
+    new: new_svg_formatter,
+};
+
+fn new_svg_formatter() -> Box<DynFormatter> {
+    Box::new(SvgFormatter::new())
+}
+
+impl Formatter for SvgFormatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        let x = self.xoffset;
+        let mut y = self.yoffset.unwrap_or(self.fontsize);
+        let ystep = self.ystep.unwrap_or(self.fontsize + 5);
+        let linenowidth = self.linenowidth.unwrap_or(3 * ystep);
+        // the lines are collected first, since the size of the image has to
+        // be written before them
+        let mut body = Vec::new();
+        let lineno_style = self.get_style(TokenType::Comment).to_owned();
+        let (linenos, linenostep) = (self.linenos, cmp::max(self.linenostep, 1));
+        let mut lineno = self.linenostart;
+        let write_lineno = |out: &mut Vec<u8>, lineno: usize, y: usize| -> Result<()> {
+            if linenos && lineno % linenostep == 0 {
+                wf!(out, "<text x=\"{}\" y=\"{}\"{} text-anchor=\"end\">{}</text>",
+                    x + linenowidth, y, lineno_style, lineno);
+            }
+            Ok(())
+        };
+        let line_x = if linenos { x + linenowidth + ystep } else { x };
+        try!(write_lineno(&mut body, lineno, y));
+        wf!(body, "<text x=\"{}\" y=\"{}\" xml:space=\"preserve\">", line_x, y);
+        let spacehack = self.spacehack;
+        let mut column = 0;
+        let mut max_column = 0;
+        for tok in source {
+            let style = self.get_style(tok.ttype);
+            let parts = tok.text.split('\n').collect::<Vec<_>>();
+            let lastpart = parts.len() - 1;
+            for (i, part) in parts.into_iter().enumerate() {
+                if !part.is_empty() {
+                    let mut text = String::with_capacity(part.len());
+                    for ch in part.chars() {
+                        if ch == '\t' {
+                            let width = 8 - column % 8;
+                            text.extend((0..width).map(|_| ' '));
+                            column += width;
+                        } else {
+                            text.push(ch);
+                            column += 1;
+                        }
+                    }
+                    let mut text = escape_xml(&text);
+                    if spacehack {
+                        text = text.replace(' ', "&#160;");
+                    }
+                    if style.is_empty() {
+                        w!(body, text);
+                    } else {
+                        wf!(body, "<tspan{}>{}</tspan>", style, text);
+                    }
+                    max_column = cmp::max(max_column, column);
+                }
+                if i != lastpart {
+                    w!(body, "</text>\n");
+                    y += ystep;
+                    lineno += 1;
+                    column = 0;
+                    try!(write_lineno(&mut body, lineno, y));
+                    wf!(body, "<text x=\"{}\" y=\"{}\" xml:space=\"preserve\">", line_x, y);
+                }
+            }
+        }
+        w!(body, "</text>");
+        if self.nowrap {
+            return out.write_all(&body);
+        }
+        let width = line_x + (max_column * self.fontsize * 3 + 4) / 5;
+        let height = y + ystep.saturating_sub(self.fontsize);
+        w!(out, "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+        w!(out, "<!DOCTYPE svg PUBLIC \"-//W3C//DTD SVG 1.0//EN\" \
+                 \"http://www.w3.org/TR/2001/REC-SVG-20010904/DTD/svg10.dtd\">\n");
+        wf!(out, "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" \
+                  viewBox=\"0 0 {0} {1}\">\n", width, height);
+        wf!(out, "<g font-family=\"{}\" font-size=\"{}px\">\n",
+            escape_xml(&self.fontfamily), self.fontsize);
+        try!(out.write_all(&body));
+        w!(out, "</g></svg>\n");
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatter for terminals with 16-color ANSI escape sequences.
+
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use token::{Token, TokenType};
+
+/// A color scheme: for each token type, the color on light and on dark
+/// backgrounds.
+///
+/// Colors are the names `black`, `red`, `green`, `yellow`, `blue`,
+/// `magenta`, `cyan` and `gray`, their `bright` variants and `white`, or
+/// the empty string for no color.  A color can be wrapped in `*...*` for
+/// bold, `_..._` for underline and `+...+` for blinking text.  Token types
+/// without an entry use the colors of their parent type.
+pub type ColorScheme = &'static [(TokenType, &'static str, &'static str)];
+
+/// The default color scheme.
+pub static TERMINAL_COLORS: ColorScheme = &[
+    (TokenType::Whitespace,        "gray",        "brightblack"),
+    (TokenType::Comment,           "gray",        "brightblack"),
+    (TokenType::CommentPreproc,    "cyan",        "brightcyan"),
+    (TokenType::Keyword,           "blue",        "brightblue"),
+    (TokenType::KeywordType,       "cyan",        "brightcyan"),
+    (TokenType::OperatorWord,      "magenta",     "brightmagenta"),
+    (TokenType::NameBuiltin,       "cyan",        "brightcyan"),
+    (TokenType::NameFunction,      "green",       "brightgreen"),
+    (TokenType::NameNamespace,     "_cyan_",      "_brightcyan_"),
+    (TokenType::NameClass,         "_green_",     "_brightgreen_"),
+    (TokenType::NameException,     "cyan",        "brightcyan"),
+    (TokenType::NameDecorator,     "brightblack", "gray"),
+    (TokenType::NameVariable,      "red",         "brightred"),
+    (TokenType::NameConstant,      "red",         "brightred"),
+    (TokenType::NameAttribute,     "cyan",        "brightcyan"),
+    (TokenType::NameTag,           "brightblue",  "brightblue"),
+    (TokenType::String,            "yellow",      "yellow"),
+    (TokenType::Number,            "blue",        "brightblue"),
+    (TokenType::GenericDeleted,    "brightred",   "brightred"),
+    (TokenType::GenericInserted,   "green",       "brightgreen"),
+    (TokenType::GenericHeading,    "**",          "**"),
+    (TokenType::GenericSubheading, "*magenta*",   "*brightmagenta*"),
+    (TokenType::GenericPrompt,     "**",          "**"),
+    (TokenType::GenericError,      "brightred",   "brightred"),
+    (TokenType::Error,             "_brightred_", "_brightred_"),
+];
+
+const RESET: &'static str = "\x1b[39;49;00m";
+
+// Return the escape sequence that starts the given color.
+fn ansi_start(color: &str) -> String {
+    let mut color = color;
+    let mut result = String::new();
+    for &(marker, code) in &[('+', "\x1b[05m"), ('*', "\x1b[01m"), ('_', "\x1b[04m")] {
+        if color.len() >= 2 && color.starts_with(marker) && color.ends_with(marker) {
+            result.push_str(code);
+            color = &color[1..color.len() - 1];
+        }
+    }
+    let (base, offset) = if color.starts_with("bright") {
+        (&color[6..], 90)
+    } else {
+        (color, 30)
+    };
+    let code = match base {
+        "black" => 0,
+        "red" => 1,
+        "green" => 2,
+        "yellow" => 3,
+        "blue" => 4,
+        "magenta" => 5,
+        "cyan" => 6,
+        "gray" => 7,
+        // like in Pygments, white is rendered as bold
+        "white" => return result + "\x1b[01m",
+        _ => return result,
+    };
+    result + &format!("\x1b[{}m", offset + code)
+}
+
+/// Format tokens with ANSI color sequences for 16-color terminals.
+///
+/// Colors are reset at the end of every line, so that pagers like `less -R`
+/// display each line correctly on its own.
+pub struct TerminalFormatter {
+    darkbg: bool,
+    linenos: bool,
+    colorscheme: ColorScheme,
+    color_map: HashMap<TokenType, String>,
+}
+
+impl TerminalFormatter {
+    pub fn new() -> Self {
+        TerminalFormatter {
+            darkbg: false,
+            linenos: false,
+            colorscheme: TERMINAL_COLORS,
+            color_map: HashMap::new(),
+        }
+    }
+
+    add_option!(darkbg,      with_darkbg,      bool);
+    add_option!(linenos,     with_linenos,     bool);
+    add_option!(colorscheme, with_colorscheme, ColorScheme);
+
+    fn get_color(&mut self, ttype: TokenType) -> &str {
+        let (scheme, darkbg) = (self.colorscheme, self.darkbg);
+        self.color_map.entry(ttype).or_insert_with(|| {
+            ttype.ancestors()
+                 .filter_map(|t| scheme.iter().find(|&&(st, _, _)| st == t))
+                 .next()
+                 .map(|&(_, light, dark)| ansi_start(if darkbg { dark } else { light }))
+                 .unwrap_or_default()
+        })
+    }
+}
+
+pub static TERMINAL_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "Terminal",
+    aliases: &["terminal", "console", "ansi"],
+    filenames: &["*.ansi"],
+    new: new_terminal_formatter,
+};
+
+fn new_terminal_formatter() -> Box<DynFormatter> {
+    Box::new(TerminalFormatter::new())
+}
+
+impl Formatter for TerminalFormatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        let linenos = self.linenos;
+        let mut lineno = 1;
+        if linenos {
+            wf!(out, "{:04}: ", lineno);
+        }
+        for tok in source {
+            let color = self.get_color(tok.ttype);
+            let parts = tok.text.split('\n').collect::<Vec<_>>();
+            let lastpart = parts.len() - 1;
+            for (i, part) in parts.into_iter().enumerate() {
+                if !part.is_empty() {
+                    if color.is_empty() {
+                        w!(out, part);
+                    } else {
+                        wf!(out, "{}{}{}", color, part, RESET);
+                    }
+                }
+                if i != lastpart {
+                    w!(out, "\n");
+                    lineno += 1;
+                    if linenos {
+                        wf!(out, "{:04}: ", lineno);
+                    }
+                }
+            }
+        }
+        if linenos {
+            w!(out, "\n");
+        }
+        Ok(())
+    }
+}
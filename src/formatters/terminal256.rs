@@ -0,0 +1,205 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Formatters for terminals with 256 colors or 24-bit "true color".
+
+use std::collections::HashMap;
+use std::io::{Result, Write};
+
+use formatter::{DynFormatter, Formatter, FormatterInfo};
+use style::{Color, Style, TokenStyle};
+use token::{Token, TokenType};
+
+// Return the color of an entry of the xterm 256-color palette, for the
+// entries above the 16 basic colors (whose values depend on the terminal).
+fn xterm_color(index: u8) -> Color {
+    const CUBE: [u8; 6] = [0x00, 0x5f, 0x87, 0xaf, 0xd7, 0xff];
+    if index >= 232 {
+        let v = 8 + (index - 232) * 10;
+        Color(v, v, v)
+    } else {
+        let i = (index - 16) as usize;
+        Color(CUBE[i / 36], CUBE[i / 6 % 6], CUBE[i % 6])
+    }
+}
+
+/// Return the index of the xterm palette entry closest to `color`.
+pub fn closest_xterm_color(color: Color) -> u8 {
+    let distance = |index: u8| {
+        let Color(r, g, b) = xterm_color(index);
+        let (dr, dg, db) = (r as i32 - color.0 as i32, g as i32 - color.1 as i32,
+                            b as i32 - color.2 as i32);
+        dr * dr + dg * dg + db * db
+    };
+    (16..256).map(|i| i as u8).min_by_key(|&i| distance(i)).unwrap()
+}
+
+// Return the escape sequences that start and end a token style.
+fn escapes(style: &TokenStyle, truecolor: bool) -> (String, String) {
+    let color = |base: u8, color: Color| if truecolor {
+        format!("{};2;{};{};{}", base, color.0, color.1, color.2)
+    } else {
+        format!("{};5;{}", base, closest_xterm_color(color))
+    };
+    let mut on = Vec::new();
+    let mut off = Vec::new();
+    if let Some(fg) = style.color {
+        on.push(color(38, fg));
+        off.push("39");
+    }
+    if let Some(bg) = style.bgcolor {
+        on.push(color(48, bg));
+        off.push("49");
+    }
+    if style.bold {
+        on.push("01".into());
+    }
+    if style.underline {
+        on.push("04".into());
+    }
+    if style.italic {
+        on.push("03".into());
+    }
+    if style.bold || style.underline || style.italic {
+        off.push("00");
+    }
+    if on.is_empty() {
+        (String::new(), String::new())
+    } else {
+        (format!("\x1b[{}m", on.join(";")), format!("\x1b[{}m", off.join(";")))
+    }
+}
+
+/// Format tokens with escape sequences for 256-color terminals.
+///
+/// The colors of the style are approximated by the closest entries of the
+/// xterm palette.  Like `TerminalFormatter`, styles are ended before every
+/// newline.
+pub struct Terminal256Formatter {
+    style: Style,
+    linenos: bool,
+    truecolor: bool,
+    escape_map: HashMap<TokenType, (String, String)>,
+}
+
+impl Terminal256Formatter {
+    pub fn new() -> Self {
+        Terminal256Formatter {
+            style: Style::default(),
+            linenos: false,
+            truecolor: false,
+            escape_map: HashMap::new(),
+        }
+    }
+
+    add_option!(style,   with_style,   Style);
+    add_option!(linenos, with_linenos, bool);
+}
+
+pub static TERMINAL256_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "Terminal256",
+    aliases: &["terminal256", "console256", "256"],
+    filenames: &[],
+    new: new_terminal256_formatter,
+};
+
+fn new_terminal256_formatter() -> Box<DynFormatter> {
+    Box::new(Terminal256Formatter::new())
+}
+
+impl Formatter for Terminal256Formatter {
+    fn format<'a, I, W>(&mut self, source: I, mut out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        let mut lineno = 1;
+        if self.linenos {
+            wf!(out, "{:04}: ", lineno);
+        }
+        for tok in source {
+            let (style, truecolor) = (&self.style, self.truecolor);
+            let &mut (ref on, ref off) = self.escape_map.entry(tok.ttype).or_insert_with(
+                || escapes(style.style_for(tok.ttype), truecolor));
+            let parts = tok.text.split('\n').collect::<Vec<_>>();
+            let lastpart = parts.len() - 1;
+            for (i, part) in parts.into_iter().enumerate() {
+                if !part.is_empty() {
+                    wf!(out, "{}{}{}", on, part, off);
+                }
+                if i != lastpart {
+                    w!(out, "\n");
+                    lineno += 1;
+                    if self.linenos {
+                        wf!(out, "{:04}: ", lineno);
+                    }
+                }
+            }
+        }
+        if self.linenos {
+            w!(out, "\n");
+        }
+        Ok(())
+    }
+}
+
+/// Format tokens with 24-bit color escape sequences.
+///
+/// This renders the exact colors of the style, but needs a terminal that
+/// supports them.
+pub struct TerminalTrueColorFormatter(Terminal256Formatter);
+
+impl TerminalTrueColorFormatter {
+    pub fn new() -> Self {
+        TerminalTrueColorFormatter(Terminal256Formatter { truecolor: true,
+                                                          .. Terminal256Formatter::new() })
+    }
+
+    pub fn with_style(self, value: Style) -> Self {
+        TerminalTrueColorFormatter(self.0.with_style(value))
+    }
+
+    pub fn with_linenos(self, value: bool) -> Self {
+        TerminalTrueColorFormatter(self.0.with_linenos(value))
+    }
+}
+
+pub static TERMINAL_TRUE_COLOR_FORMATTER: FormatterInfo = FormatterInfo {
+    name: "TerminalTrueColor",
+    aliases: &["terminal16m", "console16m", "16m"],
+    filenames: &[],
+    new: new_terminal_true_color_formatter,
+};
+
+fn new_terminal_true_color_formatter() -> Box<DynFormatter> {
+    Box::new(TerminalTrueColorFormatter::new())
+}
+
+impl Formatter for TerminalTrueColorFormatter {
+    fn format<'a, I, W>(&mut self, source: I, out: W) -> Result<()>
+        where I: Iterator<Item=Token<'a>>, W: Write
+    {
+        self.0.format(source, out)
+    }
+}
@@ -24,23 +24,190 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
-use regex::Regex;
+use regex::{self, Regex};
 use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fmt;
+use std::slice;
 
-use token::{Token, TokenType};
+use filter::{Filter, Filtered};
+use token::{Position, Token, TokenType};
 use macros::{MachineDef, RuleDef};
+use regexopt::regex_opt_capturing;
 
 pub trait Lexer<'t>: Iterator<Item=Token<'t>> {
-    // Currently unclear which other methods belong here.
+    /// Return a score between 0.0 and 1.0 for how likely it is that this
+    /// lexer can handle `text`, used to guess a lexer when none is given.
+    fn analyse_text(_text: &str) -> f32 where Self: Sized {
+        0.0
+    }
+
+    /// Return an iterator that yields each token with its byte offset in
+    /// the source text.
+    fn indexed(self) -> Indexed<Self> where Self: Sized {
+        Indexed { inner: self, offset: 0 }
+    }
+
+    /// Return an iterator that yields each token with its offset, line and
+    /// column in the source text.
+    fn positioned(self) -> Positioned<'t, Self> where Self: Sized {
+        let start = Position { offset: 0, line: 1, column: 1 };
+        Positioned { source: self.source(), inner: self, scanned: start, next: start }
+    }
+
+    /// Return the whole text this lexer works on, if it is known.
+    ///
+    /// This is used by `positioned` to find the line and column of tokens.
+    fn source(&self) -> Option<&'t str> {
+        None
+    }
+
+    /// Return a lexer that passes the tokens of this one through `filter`.
+    ///
+    /// Filters keep the source offsets of the tokens they change, so
+    /// `indexed` and `positioned` on the result still refer to the source
+    /// text.
+    fn with_filter<F: Filter>(self, filter: F) -> Filtered<'t> where Self: Sized + 't {
+        Filtered::from_lexer(self, &filter)
+    }
+}
+
+impl<'t, L: Lexer<'t> + ?Sized> Lexer<'t> for Box<L> {
+    fn source(&self) -> Option<&'t str> {
+        (**self).source()
+    }
+}
+
+/// Metadata about a lexer, used to look it up at runtime.
+pub struct LexerInfo {
+    /// Human-readable name of the lexer.
+    pub name: &'static str,
+    /// Short names to select the lexer by.
+    pub aliases: &'static [&'static str],
+    /// Glob patterns for file names the lexer handles.
+    pub filenames: &'static [&'static str],
+    /// MIME types of the lexed language.
+    pub mimetypes: &'static [&'static str],
+    /// Scoring function, see `Lexer::analyse_text`.
+    pub analyse_text: fn(&str) -> f32,
+    /// Constructor for the lexer.
+    pub new: for<'t> fn(&'t str) -> Box<Lexer<'t> + 't>,
+}
+
+impl LexerInfo {
+    /// Create a lexer for the given text.
+    pub fn lexer<'t>(&self, text: &'t str) -> Box<Lexer<'t> + 't> {
+        (self.new)(text)
+    }
+}
+
+/// Token iterator adapter that tracks byte offsets.
+///
+/// The offset is the one recorded in the token by the lexer; for tokens
+/// without one, it is taken to follow directly after the previous token.
+pub struct Indexed<I> {
+    inner: I,
+    offset: usize,
+}
+
+impl<'t, I: Iterator<Item=Token<'t>>> Iterator for Indexed<I> {
+    type Item = (usize, Token<'t>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|tok| {
+            let offset = tok.offset.unwrap_or(self.offset);
+            self.offset = offset + tok.text.len();
+            (offset, tok)
+        })
+    }
+}
+
+/// Token iterator adapter that tracks offsets, lines and columns.
+///
+/// Offsets are handled like in `Indexed`.  If the lexer knows its source
+/// text, lines and columns of tokens with an offset are found by scanning
+/// the source up to it, so they stay right even if the lexer skips text or
+/// a filter changes it.  Otherwise, they are counted from the token texts.
+pub struct Positioned<'t, I> {
+    inner: I,
+    source: Option<&'t str>,
+    // position in the source up to which it has been scanned
+    scanned: Position,
+    // position directly after the previous token
+    next: Position,
+}
+
+// Move `pos` to the end of `text`, which starts at it.
+fn advance(pos: &mut Position, text: &str) {
+    pos.offset += text.len();
+    match text.rfind('\n') {
+        Some(idx) => {
+            pos.line += text.matches('\n').count();
+            pos.column = text[idx + 1..].chars().count() + 1;
+        }
+        None => pos.column += text.chars().count(),
+    }
+}
+
+impl<'t, I: Iterator<Item=Token<'t>>> Iterator for Positioned<'t, I> {
+    type Item = (Position, Token<'t>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|tok| {
+            let mut pos = self.next;
+            match (tok.offset, self.source) {
+                (Some(offset), Some(source)) if source.is_char_boundary(offset) => {
+                    if offset < self.scanned.offset {
+                        self.scanned = Position { offset: 0, line: 1, column: 1 };
+                    }
+                    let skipped = &source[self.scanned.offset..offset];
+                    advance(&mut self.scanned, skipped);
+                    pos = self.scanned;
+                }
+                (Some(offset), _) => pos.offset = offset,
+                (None, _) => {}
+            }
+            self.next = pos;
+            advance(&mut self.next, &tok.text);
+            (pos, tok)
+        })
+    }
 }
 
 #[derive(Debug, PartialEq, Eq)]
 pub struct Rule(Regex, MatchAction, StateAction);
 
+/// Getter for the machine of another lexer, used to delegate matched text.
+#[derive(Clone, Copy)]
+pub struct MachineRef(pub fn() -> &'static Machine);
+
+impl PartialEq for MachineRef {
+    fn eq(&self, other: &MachineRef) -> bool {
+        self.0() as *const Machine == other.0() as *const Machine
+    }
+}
+
+impl Eq for MachineRef { }
+
+impl fmt::Debug for MachineRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "MachineRef({:p})", self.0())
+    }
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MatchAction {
     Single(TokenType),
-    ByGroups(&'static [TokenType]),
+    ByGroups(&'static [GroupAction]),
+    /// Lex the whole match with another machine, starting in "root".
+    Using(MachineRef),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GroupAction {
+    Single(TokenType),
+    /// Lex the group's text with another machine, starting in "root".
+    Using(MachineRef),
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -55,36 +222,272 @@ pub enum StateAction {
 
 pub struct State(Vec<Rule>);
 
-pub struct Machine(BTreeMap<&'static str, State>);
+/// A compiled state machine.
+///
+/// Besides the compiled states, the machine keeps its definition and those
+/// of the machines it inherits from, so that derived machines can resolve
+/// `include` and `inherit` against the whole chain.
+pub struct Machine {
+    states: BTreeMap<&'static str, State>,
+    defs: Vec<MachineDef>,
+}
+
+/// Find the definition of a state, looking at the definitions from
+/// `layer` on towards the base machines.
+fn find_state(defs: &[MachineDef], layer: usize, state: &str)
+              -> Option<(usize, &'static [RuleDef])> {
+    for (i, def) in defs.iter().enumerate().skip(layer) {
+        for &(statename, statedef) in *def {
+            if statename == state {
+                return Some((i, statedef));
+            }
+        }
+    }
+    None
+}
+
+/// The kind of error found in a machine definition.
+#[derive(Debug)]
+pub enum MachineErrorKind {
+    /// The regex of the rule doesn't compile.
+    InvalidRegex(regex::Error),
+    /// The rule pushes or includes a state that isn't defined.
+    UnknownState(&'static str),
+    /// The state includes itself, directly or indirectly.
+    RecursiveInclude(&'static str),
+    /// The machine has no "root" state.
+    MissingRoot,
+    /// The rule pops the last state off the stack when reached from the
+    /// given state.
+    PopBelowBottom(&'static str),
+    /// The rule has a special state action like `#pop:N` that is malformed.
+    InvalidStateAction(&'static str),
+}
+
+/// An error in a machine definition.
+///
+/// `state` and `rule` locate the offending rule in the definition: for
+/// rules spliced in by `include` or `inherit`, they name the state that
+/// defines the rule, not the one including it.
+#[derive(Debug)]
+pub struct MachineError {
+    pub state: &'static str,
+    pub rule: Option<usize>,
+    pub kind: MachineErrorKind,
+}
+
+impl MachineError {
+    fn new(state: &'static str, rule: usize, kind: MachineErrorKind) -> MachineError {
+        MachineError { state: state, rule: Some(rule), kind: kind }
+    }
+}
+
+impl fmt::Display for MachineError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "in state {:?}", self.state));
+        if let Some(rule) = self.rule {
+            try!(write!(f, ", rule {}", rule));
+        }
+        match self.kind {
+            MachineErrorKind::InvalidRegex(ref err) =>
+                write!(f, ": invalid regex: {}", err),
+            MachineErrorKind::UnknownState(name) =>
+                write!(f, ": unknown state {:?}", name),
+            MachineErrorKind::RecursiveInclude(name) =>
+                write!(f, ": state {:?} includes itself", name),
+            MachineErrorKind::MissingRoot =>
+                write!(f, ": no root state defined"),
+            MachineErrorKind::PopBelowBottom(from) =>
+                write!(f, ": popping from state {:?} empties the stack", from),
+            MachineErrorKind::InvalidStateAction(action) =>
+                write!(f, ": invalid state action {:?}", action),
+        }
+    }
+}
+
+impl Error for MachineError {
+    fn description(&self) -> &str {
+        "invalid machine definition"
+    }
+}
+
+/// Convert pushes of the special names `#pop`, `#pop:N` and `#push`, which
+/// the machine macros can't tell apart from state names, into the
+/// corresponding state actions.
+fn parse_special_action(action: StateAction) -> Result<StateAction, MachineErrorKind> {
+    let name = match action {
+        StateAction::Push(name) if name.starts_with('#') => name,
+        _ => return Ok(action),
+    };
+    match name {
+        "#pop" => Ok(StateAction::Pop),
+        "#push" => Ok(StateAction::PushSelf(1)),
+        _ if name.starts_with("#pop:") => match name[5..].parse() {
+            Ok(1) => Ok(StateAction::Pop),
+            Ok(n) if n > 1 => Ok(StateAction::PopMulti(n)),
+            _ => Err(MachineErrorKind::InvalidStateAction(name)),
+        },
+        _ => Err(MachineErrorKind::InvalidStateAction(name)),
+    }
+}
+
+/// A compiled rule, together with the state and index of its definition.
+type LocatedRule = (Rule, (&'static str, usize));
 
 impl Machine {
     pub fn get_state<'a>(&'a self, state: &str) -> &'a State {
-        &self.0[state]
+        &self.states[state]
     }
 
+    /// Convert a machine definition, panicking if it is invalid.
     pub fn convert(machine: MachineDef) -> Machine {
-        let mut map = BTreeMap::new();
-        for &(statename, statedef) in machine {
-            let mut rules = Vec::new();
-            for ruledef in statedef {
-                // TODO: CHECK stuff!
-                match *ruledef {
-                    RuleDef::Regex(rx, maction, saction) => {
-                        rules.push(Rule(Regex::new(rx).unwrap(), maction, saction));
+        Machine::try_convert(machine).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Convert a machine that extends and overrides the states of `base`,
+    /// panicking if it is invalid.
+    ///
+    /// States not defined in `machine` are taken over from `base`; an
+    /// `inherit` rule in a state splices in the base's rules for it.
+    pub fn convert_with_base(machine: MachineDef, base: &Machine) -> Machine {
+        Machine::try_convert_with_base(machine, base).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Convert a machine definition, checking it for errors.
+    pub fn try_convert(machine: MachineDef) -> Result<Machine, MachineError> {
+        Machine::resolve(vec![machine])
+    }
+
+    /// Convert a machine that extends `base`, checking it for errors.
+    pub fn try_convert_with_base(machine: MachineDef, base: &Machine)
+                                 -> Result<Machine, MachineError> {
+        let mut defs = vec![machine];
+        defs.extend(base.defs.iter().cloned());
+        Machine::resolve(defs)
+    }
+
+    fn resolve(defs: Vec<MachineDef>) -> Result<Machine, MachineError> {
+        let mut states = BTreeMap::new();
+        let mut locations = BTreeMap::new();
+        for def in &defs {
+            for &(statename, _) in *def {
+                if states.contains_key(statename) {
+                    continue;
+                }
+                let (layer, statedef) = find_state(&defs, 0, statename).unwrap();
+                let mut rules = Vec::new();
+                let mut stack = vec![statename];
+                try!(Machine::compile_rules(&defs, layer, statename, statedef,
+                                            &mut stack, &mut rules));
+                let (rules, locs): (Vec<_>, Vec<_>) = rules.into_iter().unzip();
+                states.insert(statename, State(rules));
+                locations.insert(statename, locs);
+            }
+        }
+        let machine = Machine { states: states, defs: defs };
+        try!(machine.check(&locations));
+        Ok(machine)
+    }
+
+    fn compile_rules(defs: &[MachineDef], layer: usize, statename: &'static str,
+                     statedef: &'static [RuleDef], stack: &mut Vec<&'static str>,
+                     rules: &mut Vec<LocatedRule>) -> Result<(), MachineError> {
+        for (i, ruledef) in statedef.iter().enumerate() {
+            let compile = |rx: &str| Regex::new(rx).map_err(
+                |err| MachineError::new(statename, i, MachineErrorKind::InvalidRegex(err)));
+            let special = |saction| parse_special_action(saction).map_err(
+                |kind| MachineError::new(statename, i, kind));
+            match *ruledef {
+                RuleDef::Regex(rx, maction, saction) => {
+                    rules.push((Rule(try!(compile(rx)), maction, try!(special(saction))),
+                                (statename, i)));
+                }
+                RuleDef::Words(words, prefix, suffix, maction, saction) => {
+                    let rx = regex_opt_capturing(words, prefix, suffix);
+                    rules.push((Rule(try!(compile(&rx)), maction, try!(special(saction))),
+                                (statename, i)));
+                }
+                RuleDef::Default(saction) => {
+                    rules.push((Rule(try!(compile("")), MatchAction::Single(TokenType::Text),
+                                     try!(special(saction))), (statename, i)));
+                }
+                RuleDef::Include(other) => {
+                    if stack.contains(&other) {
+                        return Err(MachineError::new(
+                            statename, i, MachineErrorKind::RecursiveInclude(other)));
                     }
-                    // Words(words, maction, saction) => {
-                    //     // XXX
-                    // }
-                    RuleDef::Default(saction) => {
-                        rules.push(Rule(Regex::new("").unwrap(),
-                                        MatchAction::Single(TokenType::Text), saction));
+                    // includes always refer to the most derived definition
+                    let (other_layer, otherdef) = try!(find_state(defs, 0, other).ok_or_else(
+                        || MachineError::new(statename, i, MachineErrorKind::UnknownState(other))));
+                    stack.push(other);
+                    try!(Machine::compile_rules(defs, other_layer, other, otherdef, stack, rules));
+                    stack.pop();
+                }
+                RuleDef::Inherit => {
+                    if let Some((base_layer, basedef)) = find_state(defs, layer + 1, statename) {
+                        try!(Machine::compile_rules(defs, base_layer, statename, basedef,
+                                                    stack, rules));
                     }
-                    _ => {}
                 }
             }
-            map.insert(statename, State(rules));
         }
-        Machine(map)
+        Ok(())
+    }
+
+    /// Check the state actions of the compiled machine: all targets must
+    /// exist, and no pop may empty the stack when starting from "root".
+    fn check(&self, locations: &BTreeMap<&'static str, Vec<(&'static str, usize)>>)
+             -> Result<(), MachineError> {
+        if !self.states.contains_key("root") {
+            return Err(MachineError { state: "root", rule: None,
+                                      kind: MachineErrorKind::MissingRoot });
+        }
+        let error = |statename: &str, i: usize, kind| {
+            let (state, rule) = locations[statename][i];
+            MachineError::new(state, rule, kind)
+        };
+        for (&statename, state) in &self.states {
+            for (i, &Rule(_, _, saction)) in state.0.iter().enumerate() {
+                let targets = match saction {
+                    StateAction::Push(ref to) => slice::from_ref(to),
+                    StateAction::PushMulti(which) => which,
+                    _ => &[],
+                };
+                for &to in targets {
+                    if !self.states.contains_key(to) {
+                        return Err(error(statename, i, MachineErrorKind::UnknownState(to)));
+                    }
+                }
+            }
+        }
+        // find the minimum stack depth at which each state can be on top
+        let mut depths = BTreeMap::new();
+        let mut queue = VecDeque::new();
+        depths.insert("root", 1);
+        queue.push_back("root");
+        while let Some(statename) = queue.pop_front() {
+            let depth = depths[statename];
+            for (i, &Rule(_, _, saction)) in self.states[statename].0.iter().enumerate() {
+                let (pops, targets) = match saction {
+                    StateAction::Pop => (1, &[][..]),
+                    StateAction::PopMulti(n) => (n, &[][..]),
+                    StateAction::Push(ref to) => (0, slice::from_ref(to)),
+                    StateAction::PushMulti(which) => (0, which),
+                    StateAction::None | StateAction::PushSelf(_) => (0, &[][..]),
+                };
+                if pops >= depth {
+                    return Err(error(statename, i, MachineErrorKind::PopBelowBottom(statename)));
+                }
+                for (j, &to) in targets.iter().enumerate() {
+                    let to_depth = depth + j + 1;
+                    if depths.get(to).map_or(true, |&d| to_depth < d) {
+                        depths.insert(to, to_depth);
+                        queue.push_back(to);
+                    }
+                }
+            }
+        }
+        Ok(())
     }
 }
 
@@ -93,7 +496,10 @@ pub struct RegexLexer<'t> {
     states: Vec<&'static str>,
     topstate: &'static State,
     queue: VecDeque<Token<'t>>,
+    text: &'t str,
     rest: &'t str,
+    /// Byte offset of `rest` in the source text.
+    offset: usize,
 }
 
 impl<'t> RegexLexer<'t> {
@@ -103,7 +509,9 @@ impl<'t> RegexLexer<'t> {
                      states: vec![initstate],
                      topstate: machine.get_state(initstate),
                      queue: VecDeque::with_capacity(16),
-                     rest: text }
+                     text: text,
+                     rest: text,
+                     offset: 0 }
     }
 
     #[inline]
@@ -133,40 +541,100 @@ impl<'t> RegexLexer<'t> {
         }
     }
 
+    /// Lex `text`, found at `offset` in the source, with another machine and
+    /// queue the resulting tokens.
+    fn delegate(&mut self, machine: &'static Machine, text: &'t str, offset: usize) {
+        let mut lexer = RegexLexer::new(machine, "root", text);
+        lexer.offset = offset;
+        for tok in lexer {
+            self.queue.push_front(tok);
+        }
+    }
+
+    /// Split off the first `idx` bytes of the remaining text, returning them
+    /// with their offset.
+    fn advance(&mut self, idx: usize) -> (&'t str, usize) {
+        let (matched, rest) = self.rest.split_at(idx);
+        let offset = self.offset;
+        self.rest = rest;
+        self.offset += idx;
+        (matched, offset)
+    }
+
+    /// Return the next token, skipping over matches that produce none.
+    ///
+    /// Empty matches (such as `default` rules) only change the state; an
+    /// empty match that doesn't change it could never advance, so the rule
+    /// is skipped in that case.
     #[inline]
     fn lex_next(&mut self) -> Option<Token<'t>> {
-        for &Rule(ref rx, type_action, state_action) in &self.topstate.0 {
-            match type_action {
-                MatchAction::Single(ttype) => if let Some((_, idx)) = rx.find(self.rest) {
-                    let (matched, rest) = self.rest.split_at(idx);
-                    self.rest = rest;
-                    self.do_state_action(state_action);
-                    return Some(Token { text: matched, ttype: ttype });
-                },
-                MatchAction::ByGroups(groups) => if let Some(cap) = rx.captures(self.rest) {
-                    self.rest = &self.rest[cap.pos(0).unwrap().1..];
-                    self.do_state_action(state_action);
-                    let mut first = None;
-                    for (i, &group_ttype) in groups.iter().enumerate() {
-                        let matched = cap.at(i + 1).unwrap();
-                        let tok = Token { text: matched, ttype: group_ttype };
-                        if i == 0 {
-                            first = Some(tok);
-                        } else if !matched.is_empty() {
-                            self.queue.push_front(tok);
+        'lex: loop {
+            for &Rule(ref rx, type_action, state_action) in &self.topstate.0 {
+                match type_action {
+                    MatchAction::Single(ttype) => if let Some((_, idx)) = rx.find(self.rest) {
+                        if idx == 0 && !changes_state(state_action) {
+                            continue;
+                        }
+                        let (matched, offset) = self.advance(idx);
+                        self.do_state_action(state_action);
+                        if matched.is_empty() {
+                            continue 'lex;
+                        }
+                        return Some(Token::new(matched, ttype).with_offset(offset));
+                    },
+                    MatchAction::ByGroups(groups) => if let Some(cap) = rx.captures(self.rest) {
+                        let end = cap.pos(0).unwrap().1;
+                        if end == 0 && !changes_state(state_action) {
+                            continue;
+                        }
+                        let (_, base) = self.advance(end);
+                        self.do_state_action(state_action);
+                        for (i, &group_action) in groups.iter().enumerate() {
+                            let matched = cap.at(i + 1).unwrap_or("");
+                            let offset = base + cap.pos(i + 1).map_or(0, |(start, _)| start);
+                            match group_action {
+                                GroupAction::Single(group_ttype) => if !matched.is_empty() {
+                                    self.queue.push_front(
+                                        Token::new(matched, group_ttype).with_offset(offset));
+                                },
+                                GroupAction::Using(machine) =>
+                                    self.delegate(machine.0(), matched, offset),
+                            }
+                        }
+                        match self.queue.pop_back() {
+                            Some(tok) => return Some(tok),
+                            None => continue 'lex,
+                        }
+                    },
+                    MatchAction::Using(machine) => if let Some((_, idx)) = rx.find(self.rest) {
+                        if idx == 0 && !changes_state(state_action) {
+                            continue;
+                        }
+                        let (matched, offset) = self.advance(idx);
+                        self.do_state_action(state_action);
+                        self.delegate(machine.0(), matched, offset);
+                        match self.queue.pop_back() {
+                            Some(tok) => return Some(tok),
+                            None => continue 'lex,
                         }
                     }
-                    return first;
                 }
             }
+            if self.rest.is_empty() {
+                return None;
+            }
+            let idx = self.rest.char_indices().skip(1).next().map_or(self.rest.len(), |v| v.0);
+            let (matched, offset) = self.advance(idx);
+            return Some(Token::new(matched, TokenType::Error).with_offset(offset));
         }
-        if self.rest.is_empty() {
-            return None;
-        }
-        let idx = self.rest.char_indices().skip(1).next().map_or(self.rest.len(), |v| v.0);
-        let (matched, rest) = self.rest.split_at(idx);
-        self.rest = rest;
-        Some(Token { text: matched, ttype: TokenType::Error })
+    }
+}
+
+/// Return whether the state action changes the top of the state stack.
+fn changes_state(action: StateAction) -> bool {
+    match action {
+        StateAction::None | StateAction::PushSelf(_) => false,
+        _ => true,
     }
 }
 
@@ -181,3 +649,9 @@ impl<'t> Iterator for RegexLexer<'t> {
         }
     }
 }
+
+impl<'t> Lexer<'t> for RegexLexer<'t> {
+    fn source(&self) -> Option<&'t str> {
+        Some(self.text)
+    }
+}
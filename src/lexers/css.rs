@@ -0,0 +1,109 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Lexer for CSS.
+
+define_lexer! {
+    CssLexer,
+    CSS_MACHINE,
+    CSS_LEXER,
+    name: "CSS",
+    aliases: ["css"],
+    filenames: ["*.css"],
+    mimetypes: ["text/css"],
+}
+
+define_machine! {
+    CSS_MACHINE,
+    CSS_TOKEN_DEF,
+    [DOTALL],
+    "root": [
+        include("basics"),
+    ],
+    "basics": [
+        (r"\s+", Text),
+        (r"/\*.*?\*/", Comment),
+        (r"\{", Punctuation, "content"),
+        (r"(:{1,2})([\w-]+)", bygroups(Punctuation, NameDecorator)),
+        (r"(\.)([\w-]+)", bygroups(Punctuation, NameClass)),
+        (r"(#)([\w-]+)", bygroups(Punctuation, NameNamespace)),
+        (r"(@)([\w-]+)", bygroups(Punctuation, Keyword), "atrule"),
+        (r"[\w-]+", NameTag),
+        (r"[~^*!%&$\[\]()<>|+=@:;,./?-]", Operator),
+        (r#""(\\\\|\\"|[^"])*""#, StringDouble),
+        (r"'(\\\\|\\'|[^'])*'", StringSingle),
+    ],
+    "atrule": [
+        (r"\{", Punctuation, "atcontent"),
+        (r";", Punctuation, "#pop"),
+        include("basics"),
+    ],
+    "atcontent": [
+        (r"\}", Punctuation, "#pop:2"),
+        include("basics"),
+    ],
+    "content": [
+        (r"\s+", Text),
+        (r"\}", Punctuation, "#pop"),
+        (r";", Punctuation),
+        (r"/\*.*?\*/", Comment),
+        (r"([\w-]+)(\s*)(:)", bygroups(Keyword, Text, Punctuation), "value-start"),
+        (r"[\w-]+", Keyword),
+    ],
+    "value-start": [
+        (r";", Punctuation, "#pop"),
+        (r"\}", Punctuation, "#pop:2"),
+        include("value-values"),
+    ],
+    "function-start": [
+        (r"\)", Punctuation, "#pop"),
+        include("value-values"),
+    ],
+    "value-values": [
+        (r"\s+", Text),
+        (r"/\*.*?\*/", Comment),
+        (r"!important", CommentPreproc),
+        (r"(url)(\()([^)]*)(\))", bygroups(NameBuiltin, Punctuation, StringOther, Punctuation)),
+        (r"([\w-]+)(\()", bygroups(NameFunction, Punctuation), "function-start"),
+        (words(("inherit", "initial", "unset", "none", "auto", "normal", "bold",
+                "bolder", "lighter", "italic", "oblique", "underline", "solid",
+                "dashed", "dotted", "double", "hidden", "visible", "block", "inline",
+                "inline-block", "flex", "grid", "table", "absolute", "relative",
+                "fixed", "static", "sticky", "left", "right", "center", "top",
+                "bottom", "middle", "baseline", "transparent", "currentColor",
+                "pointer", "default", "nowrap", "pre", "pre-wrap", "scroll",
+                "uppercase", "lowercase", "capitalize"), suffix = r"\b"),
+         KeywordConstant),
+        (r"#[a-fA-F0-9]{1,8}", NumberHex),
+        (r"([+-]?(?:[0-9]+(?:\.[0-9]+)?|\.[0-9]+))([a-zA-Z%]*)",
+         bygroups(Number, KeywordType)),
+        (r"[\w-]+", Name),
+        (r#""(\\\\|\\"|[^"])*""#, StringDouble),
+        (r"'(\\\\|\\'|[^'])*'", StringSingle),
+        (r"[\[\](),/]", Punctuation),
+        (r"[~^*!%&<>|+=@:.?-]", Operator),
+    ],
+}
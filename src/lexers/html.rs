@@ -26,9 +26,23 @@
 
 //! Lexer for HTML.
 
+use lexers::css::CSS_MACHINE;
+use lexers::javascript::JAVASCRIPT_MACHINE;
+use util::html_doctype_matches;
+
 define_lexer! {
     HtmlLexer,
     HTML_MACHINE,
+    HTML_LEXER,
+    name: "HTML",
+    aliases: ["html"],
+    filenames: ["*.html", "*.htm", "*.xhtml", "*.xslt"],
+    mimetypes: ["text/html", "application/xhtml+xml"],
+    analyse_text: analyse_text,
+}
+
+fn analyse_text(text: &str) -> f32 {
+    if html_doctype_matches(text) { 0.5 } else { 0.0 }
 }
 
 define_machine! {
@@ -68,15 +82,15 @@ define_machine! {
         (r"[^\s>]+", String, "#pop"),
     ],
     "script-content": [
-        (r"[^<]+", Text),
-        (r"(<)(\s*)(/)(\s*)(script)(\s*)(>)",
-         bygroups(Punctuation, Text, Punctuation, Text, NameTag, Text, Punctuation), "#pop"),
-        (r"<", Text),
+        (r"(.*?)(<)(\s*)(/)(\s*)(script)(\s*)(>)",
+         bygroups(using(JAVASCRIPT_MACHINE), Punctuation, Text, Punctuation, Text, NameTag,
+                  Text, Punctuation), "#pop"),
+        (r".+", using(JAVASCRIPT_MACHINE)),
     ],
     "style-content": [
-        (r"[^<]+", Text),
-        (r"(<)(\s*)(/)(\s*)(style)(\s*)(>)",
-         bygroups(Punctuation, Text, Punctuation, Text, NameTag, Text, Punctuation), "#pop"),
-        (r"<", Text),
+        (r"(.*?)(<)(\s*)(/)(\s*)(style)(\s*)(>)",
+         bygroups(using(CSS_MACHINE), Punctuation, Text, Punctuation, Text, NameTag,
+                  Text, Punctuation), "#pop"),
+        (r".+", using(CSS_MACHINE)),
     ],
 }
@@ -0,0 +1,113 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Lexer for JavaScript.
+
+use util::shebang_matches;
+
+define_lexer! {
+    JavascriptLexer,
+    JAVASCRIPT_MACHINE,
+    JAVASCRIPT_LEXER,
+    name: "JavaScript",
+    aliases: ["js", "javascript"],
+    filenames: ["*.js", "*.jsm", "*.mjs"],
+    mimetypes: ["application/javascript", "application/x-javascript",
+                "text/x-javascript", "text/javascript"],
+    analyse_text: analyse_text,
+}
+
+fn analyse_text(text: &str) -> f32 {
+    if shebang_matches(text, r"node(?:js)?") { 1.0 } else { 0.0 }
+}
+
+define_machine! {
+    JAVASCRIPT_MACHINE,
+    JAVASCRIPT_TOKEN_DEF,
+    [DOTALL],
+    "commentsandwhitespace": [
+        (r"\s+", Text),
+        (r"<!--", Comment),
+        (r"//[^\n]*", CommentSingle),
+        (r"/\*.*?\*/", CommentMultiline),
+    ],
+    "slashstartsregex": [
+        include("commentsandwhitespace"),
+        (r"/(\\.|[^\[/\\\n]|\[(\\.|[^\]\\\n])*\])+/[gimuy]*", StringRegex, "#pop"),
+        default("#pop"),
+    ],
+    "root": [
+        include("commentsandwhitespace"),
+        (r"(\.[0-9]+|[0-9]+\.[0-9]*)([eE][-+]?[0-9]+)?", NumberFloat),
+        (r"0[bB][01]+", NumberBin),
+        (r"0[oO][0-7]+", NumberOct),
+        (r"0[xX][0-9a-fA-F]+", NumberHex),
+        (r"[0-9]+", NumberInteger),
+        (r"\.\.\.|=>", Punctuation),
+        (r"\+\+|--|~|&&|\?|:|\|\||(<<|>>>?|==?|!=?|[-<>+*%&|^/])=?", Operator,
+         "slashstartsregex"),
+        (r"[{(\[;,]", Punctuation, "slashstartsregex"),
+        (r"[})\].]", Punctuation),
+        (words(("for", "in", "of", "while", "do", "break", "return", "continue",
+                "switch", "case", "default", "if", "else", "throw", "try", "catch",
+                "finally", "new", "delete", "typeof", "instanceof", "void", "yield",
+                "this"), suffix = r"\b"),
+         Keyword, "slashstartsregex"),
+        (words(("var", "let", "with", "function"), suffix = r"\b"),
+         KeywordDeclaration, "slashstartsregex"),
+        (words(("abstract", "async", "await", "boolean", "byte", "char", "class",
+                "const", "debugger", "double", "enum", "export", "extends", "final",
+                "float", "goto", "implements", "import", "int", "interface", "long",
+                "native", "package", "private", "protected", "public", "short",
+                "static", "super", "synchronized", "throws", "transient",
+                "volatile"), suffix = r"\b"),
+         KeywordReserved),
+        (words(("true", "false", "null", "NaN", "Infinity", "undefined"), suffix = r"\b"),
+         KeywordConstant),
+        (words(("Array", "Boolean", "Date", "Error", "Function", "Math", "Number",
+                "Object", "Promise", "Proxy", "RegExp", "String", "Symbol",
+                "decodeURI", "decodeURIComponent", "encodeURI", "encodeURIComponent",
+                "eval", "isFinite", "isNaN", "parseFloat", "parseInt", "document",
+                "window"), suffix = r"\b"),
+         NameBuiltin),
+        (r"[$a-zA-Z_][\w$]*", NameOther),
+        (r#""(\\\\|\\"|[^"])*""#, StringDouble),
+        (r"'(\\\\|\\'|[^'])*'", StringSingle),
+        (r"`", StringBacktick, "interp"),
+    ],
+    "interp": [
+        (r"`", StringBacktick, "#pop"),
+        (r"\\\\", StringBacktick),
+        (r"\\`", StringBacktick),
+        (r"\$\{", StringInterpol, "interp-inside"),
+        (r"\$", StringBacktick),
+        (r"[^`\\$]+", StringBacktick),
+    ],
+    "interp-inside": [
+        (r"\}", StringInterpol, "#pop"),
+        include("root"),
+    ],
+}
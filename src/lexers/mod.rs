@@ -24,4 +24,129 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+//! Lexers for various languages, and functions to look them up.
+
+use std::path::Path;
+
+use lexer::{Lexer, LexerInfo};
+use util::{fnmatch, get_filetype_from_buffer};
+
+pub mod css;
 pub mod html;
+pub mod javascript;
+pub mod special;
+
+/// All builtin lexers.
+pub static LEXERS: &'static [&'static LexerInfo] = &[
+    &css::CSS_LEXER,
+    &html::HTML_LEXER,
+    &javascript::JAVASCRIPT_LEXER,
+    &special::RAW_TOKEN_LEXER,
+    &special::TEXT_LEXER,
+];
+
+/// Find a lexer by one of its aliases.
+pub fn find_lexer_by_name(alias: &str) -> Option<&'static LexerInfo> {
+    let alias = alias.to_lowercase();
+    LEXERS.iter().cloned().find(|info| info.aliases.contains(&&*alias))
+}
+
+/// Find a lexer whose file name patterns match the given file name.
+///
+/// Only the final component of the path is matched.
+pub fn find_lexer_for_filename(filename: &str) -> Option<&'static LexerInfo> {
+    lexers_for_filename(filename).next()
+}
+
+fn lexers_for_filename<'a>(filename: &'a str)
+                           -> Box<Iterator<Item=&'static LexerInfo> + 'a> {
+    let basename = Path::new(filename).file_name().and_then(|s| s.to_str()).unwrap_or("");
+    Box::new(LEXERS.iter().cloned()
+             .filter(move |info| info.filenames.iter().any(|p| fnmatch(p, basename))))
+}
+
+/// Find a lexer for the given MIME type.
+pub fn find_lexer_for_mimetype(mimetype: &str) -> Option<&'static LexerInfo> {
+    LEXERS.iter().cloned().find(|info| info.mimetypes.contains(&mimetype))
+}
+
+/// Guess the lexer for `text` from its content.
+///
+/// A file type given in an Emacs or Vim modeline wins; otherwise the lexer
+/// whose `analyse_text` gives the highest score is selected.  Since the
+/// text lexer always gives a small score, this never fails.
+pub fn guess_lexer_info(text: &str) -> &'static LexerInfo {
+    if let Some(info) = get_filetype_from_buffer(text).and_then(|ft| find_lexer_by_name(&ft)) {
+        return info;
+    }
+    best_by_score(LEXERS.iter().cloned(), text).unwrap_or(&special::TEXT_LEXER)
+}
+
+/// Guess the lexer for `text` coming from the given file name.
+///
+/// If a modeline names a lexer, that one is used.  Otherwise only lexers
+/// whose file name patterns match are considered, and if there are several
+/// the content is used to pick the best one.
+pub fn guess_lexer_info_for_filename(filename: &str, text: &str)
+                                     -> Option<&'static LexerInfo> {
+    if let Some(info) = get_filetype_from_buffer(text).and_then(|ft| find_lexer_by_name(&ft)) {
+        return Some(info);
+    }
+    let candidates = lexers_for_filename(filename).collect::<Vec<_>>();
+    if candidates.len() == 1 {
+        return Some(candidates[0]);
+    }
+    best_by_score(candidates.into_iter(), text).or_else(|| find_lexer_for_filename(filename))
+}
+
+// Return the lexer with the highest (positive) score; on ties the one
+// registered first wins.
+fn best_by_score<I>(infos: I, text: &str) -> Option<&'static LexerInfo>
+    where I: Iterator<Item=&'static LexerInfo>
+{
+    let mut best = None;
+    let mut best_score = 0.0;
+    for info in infos {
+        let score = (info.analyse_text)(text).max(0.0).min(1.0);
+        if score == 1.0 {
+            return Some(info);
+        }
+        if score > best_score {
+            best = Some(info);
+            best_score = score;
+        }
+    }
+    best
+}
+
+/// Create a lexer for `text`, guessed from its content.
+///
+/// See `guess_lexer_info` for how the lexer is chosen.
+pub fn guess_lexer<'t>(text: &'t str) -> Box<Lexer<'t> + 't> {
+    guess_lexer_info(text).lexer(text)
+}
+
+/// Create a lexer for `text`, guessed from its content and file name.
+///
+/// See `guess_lexer_info_for_filename` for how the lexer is chosen.
+pub fn guess_lexer_for_filename<'t>(filename: &str, text: &'t str)
+                                    -> Option<Box<Lexer<'t> + 't>> {
+    guess_lexer_info_for_filename(filename, text).map(|info| info.lexer(text))
+}
+
+/// Create a lexer for `text`, selected by one of its aliases.
+pub fn get_lexer_by_name<'t>(alias: &str, text: &'t str) -> Option<Box<Lexer<'t> + 't>> {
+    find_lexer_by_name(alias).map(|info| info.lexer(text))
+}
+
+/// Create a lexer for `text`, selected by the file name it comes from.
+pub fn get_lexer_for_filename<'t>(filename: &str, text: &'t str)
+                                  -> Option<Box<Lexer<'t> + 't>> {
+    find_lexer_for_filename(filename).map(|info| info.lexer(text))
+}
+
+/// Create a lexer for `text`, selected by its MIME type.
+pub fn get_lexer_for_mimetype<'t>(mimetype: &str, text: &'t str)
+                                  -> Option<Box<Lexer<'t> + 't>> {
+    find_lexer_for_mimetype(mimetype).map(|info| info.lexer(text))
+}
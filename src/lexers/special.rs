@@ -0,0 +1,163 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Special lexers.
+
+use std::char;
+
+use lexer::{Lexer, LexerInfo};
+use token::{Token, TokenType};
+
+define_lexer! {
+    TextLexer,
+    TEXT_MACHINE,
+    TEXT_LEXER,
+    name: "Text only",
+    aliases: ["text"],
+    filenames: ["*.txt"],
+    mimetypes: ["text/plain"],
+    analyse_text: analyse_text,
+}
+
+// a tiny score so plain text wins when no other lexer claims the input
+fn analyse_text(_text: &str) -> f32 {
+    0.01
+}
+
+define_machine! {
+    TEXT_MACHINE,
+    TEXT_TOKEN_DEF,
+    [DOTALL],
+    "root": [
+        (r".+", Text),
+    ],
+}
+
+/// Lexer for the token dumps written by `RawTokenFormatter`.
+///
+/// Each line has a Pygments token type name and a Python string literal,
+/// separated by a tab.  Malformed lines, and lines with a token type that
+/// doesn't exist yet, become `Error` tokens.
+pub struct RawTokenLexer<'t> {
+    text: &'t str,
+}
+
+impl<'t> RawTokenLexer<'t> {
+    pub fn new(text: &'t str) -> RawTokenLexer<'t> {
+        RawTokenLexer { text: text }
+    }
+}
+
+impl<'t> Iterator for RawTokenLexer<'t> {
+    type Item = Token<'t>;
+
+    fn next(&mut self) -> Option<Token<'t>> {
+        if self.text.is_empty() {
+            return None;
+        }
+        let end = self.text.find('\n').map_or(self.text.len(), |i| i + 1);
+        let line = &self.text[..end];
+        self.text = &self.text[end..];
+        let mut parts = line.trim_end().splitn(2, '\t');
+        let ttype = parts.next().and_then(TokenType::from_pygments_str);
+        match (ttype, parts.next().and_then(parse_python_str)) {
+            (Some(ttype), Some(value)) => Some(Token::new(value, ttype)),
+            _ => Some(Token::new(line, TokenType::Error)),
+        }
+    }
+}
+
+impl<'t> Lexer<'t> for RawTokenLexer<'t> { }
+
+pub static RAW_TOKEN_LEXER: LexerInfo = LexerInfo {
+    name: "Raw token data",
+    aliases: &["raw"],
+    filenames: &[],
+    mimetypes: &["application/x-pygments-tokens"],
+    analyse_text: <RawTokenLexer<'static> as Lexer<'static>>::analyse_text,
+    new: new_raw_token_lexer,
+};
+
+fn new_raw_token_lexer<'t>(text: &'t str) -> Box<Lexer<'t> + 't> {
+    Box::new(RawTokenLexer::new(text))
+}
+
+// Parse a Python string literal, as written by `repr()`.
+fn parse_python_str(literal: &str) -> Option<String> {
+    let literal = if literal.starts_with('u') { &literal[1..] } else { literal };
+    let quote = match literal.chars().next() {
+        Some(q) if q == '\'' || q == '"' => q,
+        _ => return None,
+    };
+    if literal.len() < 2 || !literal.ends_with(quote) {
+        return None;
+    }
+    let mut result = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(ch) = chars.next() {
+        if ch == quote {
+            return None;
+        }
+        if ch != '\\' {
+            result.push(ch);
+            continue;
+        }
+        let esc = match chars.next() {
+            Some(esc) => esc,
+            None => return None,
+        };
+        let hex_digits = match esc {
+            'x' => 2,
+            'u' => 4,
+            'U' => 8,
+            _ => 0,
+        };
+        if hex_digits > 0 {
+            let digits = chars.by_ref().take(hex_digits).collect::<String>();
+            if digits.len() != hex_digits {
+                return None;
+            }
+            match u32::from_str_radix(&digits, 16).ok().and_then(char::from_u32) {
+                Some(ch) => result.push(ch),
+                None => return None,
+            }
+            continue;
+        }
+        result.push(match esc {
+            '\\' | '\'' | '"' => esc,
+            'n' => '\n',
+            'r' => '\r',
+            't' => '\t',
+            'a' => '\x07',
+            'b' => '\x08',
+            'f' => '\x0c',
+            'v' => '\x0b',
+            '0' => '\0',
+            _ => return None,
+        });
+    }
+    Some(result)
+}
@@ -32,14 +32,104 @@
 extern crate lazy_static;
 extern crate typed_arena;
 extern crate regex;
+#[cfg(feature = "serde")]
+extern crate serde;
 
 #[macro_use]
 pub mod macros;
 pub mod token;
 pub mod lexer;
+pub mod regexopt;
 pub mod formatter;
+pub mod style;
+pub mod filter;
+pub mod util;
 
 pub mod lexers;
+pub mod filters;
+pub mod formatters;
+pub mod styles;
+
+use std::cell::RefCell;
+use std::fmt;
+use std::io::{self, Write};
+use std::str;
+
+use filter::{Filter, TokenStream};
+use formatter::Formatter;
+use lexer::LexerInfo;
+
+/// Highlight `code` with the given lexer and formatter, writing to `out`.
+///
+/// The tokens are passed through `filters` in order before formatting.
+/// For example, `highlight(code, &HTML_LEXER, &[], &mut HtmlFormatter::new(),
+/// stdout())`.
+///
+/// Only `out` is written to.  Files that the output refers to, such as the
+/// `cssfile` of an `HtmlFormatter`, must be written separately, e.g. with
+/// `HtmlFormatter::write_cssfile`.
+pub fn highlight<F, W>(code: &str, lexer: &LexerInfo, filters: &[Box<Filter>],
+                       formatter: &mut F, out: W) -> io::Result<()>
+    where F: Formatter, W: Write
+{
+    let mut stream: TokenStream = Box::new(lexer.lexer(code));
+    for filter in filters {
+        stream = filter.filter(stream);
+    }
+    formatter.format(stream, out)
+}
+
+/// Highlight `code` with the given lexer, filters and formatter, and return
+/// the output as a string.
+pub fn highlight_to_string<F>(code: &str, lexer: &LexerInfo, filters: &[Box<Filter>],
+                              formatter: &mut F) -> io::Result<String>
+    where F: Formatter
+{
+    let mut out = Vec::new();
+    try!(highlight(code, lexer, filters, formatter, &mut out));
+    String::from_utf8(out).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Highlighted code that formats itself when displayed, e.g. with
+/// `format!("{}", Highlighted::new(code, &CSS_LEXER, HtmlFormatter::new()))`.
+///
+/// Any error from the formatter becomes a `fmt::Error`.
+pub struct Highlighted<'c, F> {
+    code: &'c str,
+    lexer: &'c LexerInfo,
+    formatter: RefCell<F>,
+    filters: Vec<Box<Filter>>,
+}
+
+impl<'c, F: Formatter> Highlighted<'c, F> {
+    pub fn new(code: &'c str, lexer: &'c LexerInfo, formatter: F) -> Self {
+        Highlighted {
+            code: code,
+            lexer: lexer,
+            formatter: RefCell::new(formatter),
+            filters: Vec::new(),
+        }
+    }
+
+    /// Add a filter to pass the tokens through, after those added before.
+    pub fn with_filter<T: Filter + 'static>(mut self, filter: T) -> Self {
+        self.filters.push(Box::new(filter));
+        self
+    }
+
+    /// Write the highlighted code to `out`.
+    pub fn write_to<W: Write>(&self, out: W) -> io::Result<()> {
+        highlight(self.code, self.lexer, &self.filters, &mut *self.formatter.borrow_mut(), out)
+    }
+}
+
+impl<'c, F: Formatter> fmt::Display for Highlighted<'c, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = Vec::new();
+        try!(self.write_to(&mut out).map_err(|_| fmt::Error));
+        f.write_str(try!(str::from_utf8(&out).map_err(|_| fmt::Error)))
+    }
+}
 
 #[cfg(test)]
 #[path = "../test/mod.rs"]
@@ -29,8 +29,12 @@ use lexer::{MatchAction, StateAction};
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum RuleDef {
     Regex(&'static str, MatchAction, StateAction),
-    Words(&'static [&'static str], MatchAction, StateAction),
-    // Inherit(...),
+    /// Words to match, regex prefix and suffix.
+    Words(&'static [&'static str], &'static str, &'static str, MatchAction, StateAction),
+    /// Include the rules of another state here.
+    Include(&'static str),
+    /// Include the rules of the same state in the base machine here.
+    Inherit,
     Default(StateAction),
 }
 
@@ -39,53 +43,122 @@ pub type MachineDef = &'static [(&'static str, &'static [RuleDef])];
 macro_rules! machine_as_type {
     (@expr $x:expr) => { $x };
     (@slice ($($x:expr),*)) => { &[$($x),*] };
-    (@ttslice ($($x:ident),*)) => { &[$($crate::token::$x),*] };
+    (@groupslice ($($x:ident $(($($arg:tt)*))*),*)) => {
+        &[$(machine_group_action!($x $(($($arg)*))*)),*]
+    };
+}
+
+macro_rules! machine_using {
+    ($machine:path) => {{
+        fn machine() -> &'static $crate::lexer::Machine { &$machine }
+        $crate::lexer::MachineRef(machine)
+    }};
+}
+
+macro_rules! machine_group_action {
+    (using ($machine:path)) => {
+        $crate::lexer::GroupAction::Using(machine_using!($machine))
+    };
+    ($tt:ident) => {
+        $crate::lexer::GroupAction::Single($crate::token::$tt)
+    };
 }
 
 macro_rules! machine_state_action {
-    ("#pop") => ($crate::lexer::StateAction::Pop);
+    // "#pop", "#pop:N" and "#push" are resolved by Machine::try_convert
     (($($st:tt)*)) => ($crate::lexer::StateAction::PushMulti(machine_as_type!(@slice ($($st)*))));
     ($expr:expr) => ($crate::lexer::StateAction::Push($expr));
 }
 
+macro_rules! machine_rule_def {
+    ((regex $rx:expr), $ma:expr, $sa:expr) => {
+        $crate::macros::RuleDef::Regex($rx, $ma, $sa) };
+    ((words $ws:expr, $prefix:expr, $suffix:expr), $ma:expr, $sa:expr) => {
+        $crate::macros::RuleDef::Words($ws, $prefix, $suffix, $ma, $sa) };
+}
+
 macro_rules! machine_action {
-    ($rx:expr, bygroups $tts:tt) => {
-        $crate::macros::RuleDef::Regex($rx, $crate::lexer::MatchAction::ByGroups(
-            machine_as_type!(@ttslice $tts)), $crate::lexer::StateAction::None) };
-    ($rx:expr, bygroups $tts:tt, $($sa:tt)*) => {
-        $crate::macros::RuleDef::Regex($rx, $crate::lexer::MatchAction::ByGroups(
-            machine_as_type!(@ttslice $tts)), machine_state_action!($($sa)*)) };
-    ($rx:expr, $tt:ident) => {
-        $crate::macros::RuleDef::Regex($rx, $crate::lexer::MatchAction::Single(
+    ($rd:tt, using($machine:path)) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::Using(
+            machine_using!($machine)), $crate::lexer::StateAction::None) };
+    ($rd:tt, using($machine:path), $($sa:tt)*) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::Using(
+            machine_using!($machine)), machine_state_action!($($sa)*)) };
+    ($rd:tt, bygroups $tts:tt) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::ByGroups(
+            machine_as_type!(@groupslice $tts)), $crate::lexer::StateAction::None) };
+    ($rd:tt, bygroups $tts:tt, $($sa:tt)*) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::ByGroups(
+            machine_as_type!(@groupslice $tts)), machine_state_action!($($sa)*)) };
+    ($rd:tt, $tt:ident) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::Single(
             machine_as_type!(@expr $crate::token::$tt)), $crate::lexer::StateAction::None) };
-    ($rx:expr, $tt:ident, $($sa:tt)*) => {
-        $crate::macros::RuleDef::Regex($rx, $crate::lexer::MatchAction::Single(
+    ($rd:tt, $tt:ident, $($sa:tt)*) => {
+        machine_rule_def!($rd, $crate::lexer::MatchAction::Single(
             machine_as_type!(@expr $crate::token::$tt)), machine_state_action!($($sa)*)) };
 }
 
+// The regex is wrapped in a group so that the anchor applies to all of its
+// alternatives; machine_rule adds the closing parenthesis.
 macro_rules! machine_regex_prefix {
-    ()  => { r"\A" };
-    (,) => { r"\A" };
+    ()  => { r"\A(?:" };
+    (,) => { r"\A(?:" };
     (IGNORECASE, $($opts:tt)*) => { concat!("(?i)", machine_regex_prefix!($($opts),*)) };
     (DOTALL,     $($opts:tt)*) => { concat!("(?s)", machine_regex_prefix!($($opts),*)) };
 }
 
+macro_rules! machine_words_arg {
+    ($which:ident) => { "" };
+    (prefix, prefix = $v:expr $(, $kw:ident = $kv:expr)*) => { $v };
+    (suffix, suffix = $v:expr $(, $kw:ident = $kv:expr)*) => { $v };
+    ($which:ident, $k:ident = $v:expr $(, $kw:ident = $kv:expr)*) => {
+        machine_words_arg!($which $(, $kw = $kv)*)
+    };
+}
+
 macro_rules! machine_rule {
+    ([$($opt:tt)*], include ($state:expr)) => {
+        $crate::macros::RuleDef::Include($state)
+    };
+    ([$($opt:tt)*], inherit) => {
+        $crate::macros::RuleDef::Inherit
+    };
+    ([$($opt:tt)*], default ($($sa:tt)*)) => {
+        $crate::macros::RuleDef::Default(machine_state_action!($($sa)*))
+    };
+    ([$($opt:tt)*], (words($ws:tt $(, $kw:ident = $kv:expr)*), $($action:tt)*)) => {
+        machine_action!((words machine_as_type!(@slice $ws),
+                         concat!(machine_regex_prefix!($($opt)*,),
+                                 machine_words_arg!(prefix $(, $kw = $kv)*)),
+                         concat!(machine_words_arg!(suffix $(, $kw = $kv)*), ")")),
+                        $($action)*)
+    };
     ([$($opt:tt)*], ($rx:expr, $($action:tt)*)) => {
-        machine_action!(concat!(machine_regex_prefix!($($opt)*,), $rx), $($action)*)
+        machine_action!((regex concat!(machine_regex_prefix!($($opt)*,), $rx, ")")),
+                        $($action)*)
     };
 }
 
 #[macro_export]
 macro_rules! define_machine {
-    ($name:ident, $raw_name:ident, $opt:tt,
-     $($state:tt : [$($statett:tt),* $(,)*]),* $(,)*) =>
+    (@def $raw_name:ident, $opt:tt,
+     $($state:tt : [$($statett:tt $(($($arg:tt)*))*),* $(,)*]),* $(,)*) =>
     {
-        const $raw_name: $crate::macros::MachineDef = &[$(
+        pub const $raw_name: $crate::macros::MachineDef = &[$(
             (machine_as_type!(@expr $state), &[$(
-                machine_rule!($opt, $statett)
+                machine_rule!($opt, $statett $(($($arg)*))*)
             ),*])
         ),*];
+    };
+    ($name:ident, $raw_name:ident, $opt:tt, inherit $base:path, $($states:tt)*) => {
+        define_machine!(@def $raw_name, $opt, $($states)*);
+        lazy_static! {
+            pub static ref $name: $crate::lexer::Machine =
+                $crate::lexer::Machine::convert_with_base($raw_name, &$base);
+        }
+    };
+    ($name:ident, $raw_name:ident, $opt:tt, $($states:tt)*) => {
+        define_machine!(@def $raw_name, $opt, $($states)*);
         lazy_static! {
             pub static ref $name: $crate::lexer::Machine =
                 $crate::lexer::Machine::convert($raw_name);
@@ -97,7 +170,27 @@ macro_rules! define_machine {
 macro_rules! define_lexer {
     ($name:ident,
      $machine_name:ident,
+     $info_name:ident,
+     name: $lexer_name:expr,
+     aliases: [$($alias:expr),*],
+     filenames: [$($filename:expr),*],
+     mimetypes: [$($mimetype:expr),*]
+     $(, analyse_text: $analyse:path)* $(,)*
     ) => {
+        pub static $info_name: $crate::lexer::LexerInfo = $crate::lexer::LexerInfo {
+            name: $lexer_name,
+            aliases: &[$($alias),*],
+            filenames: &[$($filename),*],
+            mimetypes: &[$($mimetype),*],
+            analyse_text: <$name<'static> as $crate::lexer::Lexer<'static>>::analyse_text,
+            new: {
+                fn new<'t>(text: &'t str) -> Box<$crate::lexer::Lexer<'t> + 't> {
+                    Box::new($name::new(text))
+                }
+                new
+            },
+        };
+
         pub struct $name<'t> {
             inner: $crate::lexer::RegexLexer<'t>
         }
@@ -119,7 +212,17 @@ macro_rules! define_lexer {
             }
         }
 
-        impl<'t> $crate::lexer::Lexer<'t> for $name<'t> { }
+        impl<'t> $crate::lexer::Lexer<'t> for $name<'t> {
+            fn source(&self) -> Option<&'t str> {
+                $crate::lexer::Lexer::source(&self.inner)
+            }
+
+            $(
+                fn analyse_text(text: &str) -> f32 {
+                    $analyse(text)
+                }
+            )*
+        }
     }
 }
 
@@ -0,0 +1,160 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! An algorithm that generates optimized regexes for matching long lists of
+//! literal strings.
+//!
+//! This is a port of Pygments' `regexopt` module.
+
+use regex::quote;
+
+/// Return the longest common prefix of all strings.
+fn common_prefix<'a>(strings: &[&'a str]) -> &'a str {
+    let first = strings[0];
+    let mut end = first.len();
+    for s in &strings[1..] {
+        let mut common = 0;
+        for (a, b) in first[..end].chars().zip(s.chars()) {
+            if a != b {
+                break;
+            }
+            common += a.len_utf8();
+        }
+        end = common;
+    }
+    &first[..end]
+}
+
+/// Return the length in bytes of the longest common suffix of all strings.
+fn common_suffix_len(strings: &[&str]) -> usize {
+    let first = strings[0];
+    let mut len = first.len();
+    for s in &strings[1..] {
+        let mut common = 0;
+        for (a, b) in first[first.len() - len..].chars().rev().zip(s.chars().rev()) {
+            if a != b {
+                break;
+            }
+            common += a.len_utf8();
+        }
+        len = common;
+    }
+    len
+}
+
+/// Make a character class from single-character strings.
+fn make_charset(letters: &[&str]) -> String {
+    let mut result = String::from("[");
+    let mut dash = false;
+    for letter in letters {
+        // a dash is only literal at the end of the class
+        if *letter == "-" {
+            dash = true;
+        } else {
+            result.push_str(&quote(letter));
+        }
+    }
+    if dash {
+        result.push('-');
+    }
+    result.push(']');
+    result
+}
+
+/// Return a regex that matches any string in the sorted list of strings.
+fn regex_opt_inner(strings: &[&str], open_paren: &str) -> String {
+    let close_paren = if open_paren.is_empty() { "" } else { ")" };
+    if strings.is_empty() {
+        return String::new();
+    }
+    let first = strings[0];
+    if strings.len() == 1 {
+        return format!("{}{}{}", open_paren, quote(first), close_paren);
+    }
+    if first.is_empty() {
+        // the empty string can only be first, so the rest is optional
+        return format!("{}{}?{}", open_paren, regex_opt_inner(&strings[1..], "(?:"),
+                       close_paren);
+    }
+    if first.chars().count() == 1 {
+        // multiple one-char strings: make a charset
+        let (oneletter, rest): (Vec<&str>, Vec<&str>) =
+            strings.iter().partition(|s| s.chars().count() == 1);
+        if oneletter.len() > 1 {
+            if !rest.is_empty() {
+                return format!("{}{}|{}{}", open_paren, regex_opt_inner(&rest, ""),
+                               make_charset(&oneletter), close_paren);
+            }
+            return format!("{}{}{}", open_paren, make_charset(&oneletter), close_paren);
+        }
+    }
+    let prefix = common_prefix(strings);
+    if !prefix.is_empty() {
+        let plen = prefix.len();
+        let rest = strings.iter().map(|s| &s[plen..]).collect::<Vec<_>>();
+        return format!("{}{}{}{}", open_paren, quote(prefix),
+                       regex_opt_inner(&rest, "(?:"), close_paren);
+    }
+    let slen = common_suffix_len(strings);
+    if slen > 0 {
+        let mut rest = strings.iter().map(|s| &s[..s.len() - slen]).collect::<Vec<_>>();
+        rest.sort();
+        return format!("{}{}{}{}", open_paren, regex_opt_inner(&rest, "(?:"),
+                       quote(&first[first.len() - slen..]), close_paren);
+    }
+    // no common prefix or suffix: split off the strings starting with the
+    // same character as the first one (they are adjacent since the list is
+    // sorted) and try again on both halves
+    let firstchar = first.chars().next();
+    let split = strings.iter().position(|s| s.chars().next() != firstchar)
+                              .unwrap_or(strings.len());
+    format!("{}{}|{}{}", open_paren, regex_opt_inner(&strings[..split], ""),
+            regex_opt_inner(&strings[split..], ""), close_paren)
+}
+
+/// Return a compiled regex string that matches any string in the given list.
+///
+/// The strings to match must be literal strings, not regexes.  They will be
+/// regex-escaped.  The alternatives are wrapped in a non-capturing group,
+/// so that capturing groups in `prefix` and `suffix` keep their numbers.
+pub fn regex_opt(strings: &[&str], prefix: &str, suffix: &str) -> String {
+    regex_opt_with_paren(strings, prefix, suffix, "(?:")
+}
+
+/// Like `regex_opt`, but wrap the alternatives in a capturing group, as
+/// Pygments does.  This is used for `words` rules, so that `bygroups` can
+/// refer to the matched word.
+pub fn regex_opt_capturing(strings: &[&str], prefix: &str, suffix: &str) -> String {
+    regex_opt_with_paren(strings, prefix, suffix, "(")
+}
+
+fn regex_opt_with_paren(strings: &[&str], prefix: &str, suffix: &str, open_paren: &str)
+                        -> String {
+    let mut strings = strings.to_vec();
+    strings.sort();
+    strings.dedup();
+    format!("{}{}{}", prefix, regex_opt_inner(&strings, open_paren), suffix)
+}
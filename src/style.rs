@@ -24,3 +24,213 @@
 // (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
 // OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
 
+//! Styles map token types to colors and font attributes.
+
+use std::collections::hash_map::{self, HashMap};
+use std::error::Error;
+use std::fmt;
+
+use token::{TokenType, STANDARD_TYPES};
+
+/// An RGB color.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Color(pub u8, pub u8, pub u8);
+
+impl Color {
+    /// Parse a color in `#rgb` or `#rrggbb` notation.
+    pub fn from_hex(s: &str) -> Option<Color> {
+        if !s.starts_with('#') || !s[1..].chars().all(|c| c.is_digit(16)) {
+            return None;
+        }
+        let digit = |i: usize| u8::from_str_radix(&s[i..i + 1], 16).unwrap();
+        match s.len() {
+            4 => Some(Color(digit(1) * 17, digit(2) * 17, digit(3) * 17)),
+            7 => Some(Color(digit(1) << 4 | digit(2), digit(3) << 4 | digit(4),
+                            digit(5) << 4 | digit(6))),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Color {
+    /// Format as `#rrggbb`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+/// The resolved style of a single token type.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TokenStyle {
+    pub color: Option<Color>,
+    pub bgcolor: Option<Color>,
+    pub border: Option<Color>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    /// Font family hints, used by formatters that can switch fonts.
+    pub roman: bool,
+    pub sans: bool,
+    pub mono: bool,
+}
+
+impl TokenStyle {
+    /// Check if the style doesn't change anything from the default.
+    pub fn is_empty(&self) -> bool {
+        *self == TokenStyle::default()
+    }
+
+    // Apply a Pygments style definition like "bold #f00 bg:#fff".
+    fn apply(&mut self, def: &str) -> Result<(), String> {
+        for item in def.split_whitespace() {
+            match item {
+                "noinherit" => {}
+                "bold" => self.bold = true,
+                "nobold" => self.bold = false,
+                "italic" => self.italic = true,
+                "noitalic" => self.italic = false,
+                "underline" => self.underline = true,
+                "nounderline" => self.underline = false,
+                "roman" => self.roman = true,
+                "sans" => self.sans = true,
+                "mono" => self.mono = true,
+                _ if item.starts_with("bg:") => self.bgcolor = try!(parse_color(&item[3..])),
+                _ if item.starts_with("border:") => self.border = try!(parse_color(&item[7..])),
+                _ => self.color = try!(parse_color(item)),
+            }
+        }
+        Ok(())
+    }
+}
+
+// An empty color means no color; on error, return the invalid color.
+fn parse_color(s: &str) -> Result<Option<Color>, String> {
+    if s.is_empty() {
+        return Ok(None);
+    }
+    Color::from_hex(s).map(Some).ok_or_else(|| s.to_owned())
+}
+
+/// Error for an invalid color in a style definition.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StyleError {
+    /// Name of the style.
+    pub style: &'static str,
+    /// The entry containing the color, a token type or e.g. "background".
+    pub entry: &'static str,
+    /// The invalid color.
+    pub color: String,
+}
+
+impl fmt::Display for StyleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "style {}, {}: invalid color {:?}", self.style, self.entry, self.color)
+    }
+}
+
+impl Error for StyleError {
+    fn description(&self) -> &str {
+        "invalid color in style definition"
+    }
+}
+
+/// Static definition of a style, in the format Pygments uses.
+///
+/// Each token type's definition is a space-separated list of `bold`,
+/// `italic`, `underline` (each also with a `no` prefix), `roman`, `sans`,
+/// `mono`, a foreground color `#rrggbb`, `bg:#rrggbb` and `border:#rrggbb`.
+/// Token types inherit the definition of their parent type, unless they
+/// include `noinherit`.
+pub struct StyleDef {
+    pub name: &'static str,
+    /// Style for all tokens, like the `Token` entry of Pygments styles.
+    pub token_style: &'static str,
+    /// Background color of the whole code block.
+    pub background_color: &'static str,
+    /// Background color of highlighted lines.
+    pub highlight_color: &'static str,
+    pub styles: &'static [(TokenType, &'static str)],
+}
+
+/// A style, with the definitions for all standard token types resolved.
+#[derive(Clone, Debug)]
+pub struct Style {
+    pub name: &'static str,
+    pub background_color: Option<Color>,
+    pub highlight_color: Option<Color>,
+    base: TokenStyle,
+    styles: HashMap<TokenType, TokenStyle>,
+}
+
+impl Style {
+    /// Resolve a style definition.
+    ///
+    /// Panics if the definition contains invalid colors; use `try_from_def`
+    /// to get the error instead.
+    pub fn from_def(def: &StyleDef) -> Style {
+        match Style::try_from_def(def) {
+            Ok(style) => style,
+            Err(err) => panic!("{}", err),
+        }
+    }
+
+    /// Resolve a style definition, failing on invalid colors.
+    pub fn try_from_def(def: &StyleDef) -> Result<Style, StyleError> {
+        let wrap = |entry, color| StyleError { style: def.name, entry: entry, color: color };
+        let mut base = TokenStyle::default();
+        try!(base.apply(def.token_style).map_err(|e| wrap("Token", e)));
+        let mut style = Style {
+            name: def.name,
+            background_color: try!(parse_color(def.background_color)
+                                   .map_err(|e| wrap("background", e))),
+            highlight_color: try!(parse_color(def.highlight_color)
+                                  .map_err(|e| wrap("highlight", e))),
+            base: base,
+            styles: HashMap::new(),
+        };
+        let defs = def.styles.iter().cloned().collect::<HashMap<_, _>>();
+        let custom = def.styles.iter().map(|&(ttype, _)| ttype)
+                                      .filter(|ttype| !STANDARD_TYPES.contains(ttype));
+        for ttype in STANDARD_TYPES.iter().cloned().chain(custom) {
+            try!(style.resolve(ttype, &defs).map_err(|e| wrap(ttype.as_str(), e)));
+        }
+        Ok(style)
+    }
+
+    fn resolve(&mut self, ttype: TokenType, defs: &HashMap<TokenType, &str>)
+               -> Result<TokenStyle, String> {
+        if let Some(resolved) = self.styles.get(&ttype) {
+            return Ok(resolved.clone());
+        }
+        let def = defs.get(&ttype).cloned().unwrap_or("");
+        let mut resolved = match ttype.parent() {
+            _ if def.split_whitespace().any(|item| item == "noinherit") => self.base.clone(),
+            Some(parent) => try!(self.resolve(parent, defs)),
+            None => self.base.clone(),
+        };
+        try!(resolved.apply(def));
+        self.styles.insert(ttype, resolved.clone());
+        Ok(resolved)
+    }
+
+    /// Return the style for a token type.
+    ///
+    /// Types without an entry of their own get the style of their closest
+    /// ancestor.
+    pub fn style_for(&self, ttype: TokenType) -> &TokenStyle {
+        ttype.ancestors().filter_map(|ttype| self.styles.get(&ttype)).next()
+                         .unwrap_or(&self.base)
+    }
+
+    /// Iterate over all token types with a resolved style.
+    pub fn iter<'a>(&'a self) -> hash_map::Iter<'a, TokenType, TokenStyle> {
+        self.styles.iter()
+    }
+}
+
+impl Default for Style {
+    /// The default style, which is the one of Pygments.
+    fn default() -> Style {
+        Style::from_def(&::styles::default::DEFAULT_STYLE)
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! The default highlighting style.
+
+use style::StyleDef;
+use token::TokenType::*;
+
+pub static DEFAULT_STYLE: StyleDef = StyleDef {
+    name: "default",
+    token_style: "",
+    background_color: "#f8f8f8",
+    highlight_color: "#ffffcc",
+    styles: &[
+        (Whitespace,         "#bbbbbb"),
+        (Comment,            "italic #3D7B7B"),
+        (CommentPreproc,     "noitalic #9C6500"),
+
+        (Keyword,            "bold #008000"),
+        (KeywordPseudo,      "nobold"),
+        (KeywordType,        "nobold #B00040"),
+
+        (Operator,           "#666666"),
+        (OperatorWord,       "bold #AA22FF"),
+
+        (NameBuiltin,        "#008000"),
+        (NameFunction,       "#0000FF"),
+        (NameClass,          "bold #0000FF"),
+        (NameNamespace,      "bold #0000FF"),
+        (NameException,      "bold #CB3F38"),
+        (NameVariable,       "#19177C"),
+        (NameConstant,       "#880000"),
+        (NameLabel,          "#767600"),
+        (NameEntity,         "bold #717171"),
+        (NameAttribute,      "#687822"),
+        (NameTag,            "bold #008000"),
+        (NameDecorator,      "#AA22FF"),
+
+        (String,             "#BA2121"),
+        (StringDoc,          "italic"),
+        (StringInterpol,     "bold #A45A77"),
+        (StringEscape,       "bold #AA5D1F"),
+        (StringRegex,        "#A45A77"),
+        (StringOther,        "#008000"),
+        (Number,             "#666666"),
+
+        (GenericHeading,     "bold #000080"),
+        (GenericSubheading,  "bold #800080"),
+        (GenericDeleted,     "#A00000"),
+        (GenericInserted,    "#008400"),
+        (GenericError,       "#E40000"),
+        (GenericEmph,        "italic"),
+        (GenericStrong,      "bold"),
+        (GenericPrompt,      "bold #000080"),
+        (GenericOutput,      "#717171"),
+        (GenericTraceback,   "#04D"),
+
+        (Error,              "border:#FF0000"),
+    ],
+};
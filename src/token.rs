@@ -26,11 +26,30 @@
 
 //! Definition of tokens and token types.
 
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::RwLock;
 
+#[derive(Clone)]
 pub struct Token<'t> {
-    pub text: &'t str,
+    /// The token text; borrowed from the source unless a filter replaced it.
+    pub text: Cow<'t, str>,
     pub ttype: TokenType,
+    /// Byte offset of the token in the source text, if the lexer knows it.
+    pub offset: Option<usize>,
+}
+
+impl<'t> Token<'t> {
+    pub fn new<S: Into<Cow<'t, str>>>(text: S, ttype: TokenType) -> Token<'t> {
+        Token { text: text.into(), ttype: ttype, offset: None }
+    }
+
+    /// Set the byte offset of the token in the source text.
+    pub fn with_offset(mut self, offset: usize) -> Self {
+        self.offset = Some(offset);
+        self
+    }
 }
 
 impl<'t> fmt::Debug for Token<'t> {
@@ -39,6 +58,17 @@ impl<'t> fmt::Debug for Token<'t> {
     }
 }
 
+/// Position of a token in the source text.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Position {
+    /// Byte offset from the start of the text.
+    pub offset: usize,
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column in characters, starting at 1.
+    pub column: usize,
+}
+
 /// Defines all allowable token types.  `Error`, `Other` and `Escape`
 /// are special types that shouldn't be emitted from a lexer, but are
 /// used by the system.
@@ -143,6 +173,23 @@ pub enum TokenType {
 }
 pub use self::TokenType::*;
 
+/// All token types except `Custom` ones, in hierarchical order.
+pub static STANDARD_TYPES: &'static [TokenType] = &[
+    Error, Other, Escape, Text, Whitespace, Punctuation, Keyword, KeywordConstant,
+    KeywordDeclaration, KeywordNamespace, KeywordPseudo, KeywordReserved, KeywordType, Name,
+    NameAttribute, NameBuiltin, NameBuiltinPseudo, NameClass, NameConstant, NameDecorator,
+    NameEntity, NameException, NameFunction, NameFunctionMagic, NameProperty, NameLabel,
+    NameNamespace, NameOther, NameTag, NameVariable, NameVariableClass, NameVariableGlobal,
+    NameVariableInstance, NameVariableMagic, String, StringAffix, StringBacktick, StringChar,
+    StringDelimiter, StringDoc, StringDouble, StringEscape, StringHeredoc, StringInterpol,
+    StringOther, StringRegex, StringSingle, StringSymbol, Number, NumberBin, NumberFloat,
+    NumberHex, NumberInteger, NumberIntegerLong, NumberOct, Literal, LiteralDate, Operator,
+    OperatorWord, Comment, CommentHashbang, CommentMultiline, CommentPreproc, CommentPreprocFile,
+    CommentSingle, CommentSpecial, Generic, GenericDeleted, GenericEmph, GenericError,
+    GenericHeading, GenericInserted, GenericOutput, GenericPrompt, GenericStrong,
+    GenericSubheading, GenericTraceback,
+];
+
 impl TokenType {
     pub fn as_str(&self) -> &'static str {
         match *self {
@@ -306,13 +353,38 @@ impl TokenType {
             GenericStrong => "gs",
             GenericSubheading => "gu",
             GenericTraceback => "gt",
-            // TODO: this needs new static strings
-            Custom(s) => s,
+            Custom(s) => custom_short_str(s),
         }
     }
 
-    pub fn from_str(s: &'static str) -> Self {
-        match s {
+    /// Return the token type with the given dotted name.
+    ///
+    /// Names that are not standard types create a custom type, which is a
+    /// subtype of the type named by everything before the last dot.  Custom
+    /// types are never freed, so use `from_existing_str` for names from
+    /// untrusted input.
+    pub fn from_str(s: &str) -> Self {
+        TokenType::standard_from_str(s).unwrap_or_else(
+            || Custom(CUSTOM_TYPES.write().unwrap().intern(s).0))
+    }
+
+    /// Return the standard or already created custom type with the given
+    /// dotted name, or `None` if there is none.
+    pub fn from_existing_str(s: &str) -> Option<Self> {
+        TokenType::standard_from_str(s).or_else(
+            || CUSTOM_TYPES.read().unwrap().names.get(s).map(|&(name, _)| Custom(name)))
+    }
+
+    /// Return the custom subtype `name` of this type.
+    ///
+    /// For example, `NameBuiltin.subtype("Jinja")` is the custom type
+    /// `Name.Builtin.Jinja`, with the short name `nb-Jinja`.
+    pub fn subtype(&self, name: &str) -> Self {
+        TokenType::from_str(&format!("{}.{}", self.as_str(), name))
+    }
+
+    fn standard_from_str(s: &str) -> Option<Self> {
+        Some(match s {
             "Error" => Error,
             "Other" => Other,
             "Escape" => Escape,
@@ -390,7 +462,307 @@ impl TokenType {
             "Generic.Strong" => GenericStrong,
             "Generic.Subheading" => GenericSubheading,
             "Generic.Traceback" => GenericTraceback,
-            s => Custom(s),
+            _ => return None,
+        })
+    }
+
+    /// Return the parent type in the token hierarchy, or `None` for
+    /// top-level types.
+    ///
+    /// For example, the parent of `NameBuiltinPseudo` is `NameBuiltin`.
+    pub fn parent(&self) -> Option<TokenType> {
+        match *self {
+            Error | Other | Escape | Text | Whitespace | Punctuation | Keyword | Name | String |
+                Number | Literal | Operator | Comment | Generic => None,
+            KeywordConstant | KeywordDeclaration | KeywordNamespace | KeywordPseudo |
+                KeywordReserved | KeywordType => Some(Keyword),
+            NameAttribute | NameBuiltin | NameClass | NameConstant | NameDecorator | NameEntity |
+                NameException | NameFunction | NameProperty | NameLabel | NameNamespace |
+                NameOther | NameTag | NameVariable => Some(Name),
+            NameBuiltinPseudo => Some(NameBuiltin),
+            NameFunctionMagic => Some(NameFunction),
+            NameVariableClass | NameVariableGlobal | NameVariableInstance | NameVariableMagic =>
+                Some(NameVariable),
+            StringAffix | StringBacktick | StringChar | StringDelimiter | StringDoc |
+                StringDouble | StringEscape | StringHeredoc | StringInterpol | StringOther |
+                StringRegex | StringSingle | StringSymbol => Some(String),
+            NumberBin | NumberFloat | NumberHex | NumberInteger | NumberOct => Some(Number),
+            NumberIntegerLong => Some(NumberInteger),
+            LiteralDate => Some(Literal),
+            OperatorWord => Some(Operator),
+            CommentHashbang | CommentMultiline | CommentPreproc | CommentSingle |
+                CommentSpecial => Some(Comment),
+            CommentPreprocFile => Some(CommentPreproc),
+            GenericDeleted | GenericEmph | GenericError | GenericHeading | GenericInserted |
+                GenericOutput | GenericPrompt | GenericStrong | GenericSubheading |
+                GenericTraceback => Some(Generic),
+            Custom(name) => name.rfind('.').map(|idx| TokenType::from_str(&name[..idx])),
+        }
+    }
+
+    /// Iterate over this type and its ancestors, up to the top-level type.
+    pub fn ancestors(&self) -> Ancestors {
+        Ancestors(Some(*self))
+    }
+
+    /// Check if this type is `other` or one of its subtypes.
+    pub fn is_subtype_of(&self, other: TokenType) -> bool {
+        self.ancestors().any(|ttype| ttype == other)
+    }
+
+    /// Return the top-level type and all types down to this one.
+    ///
+    /// For example, `NameBuiltinPseudo.split()` gives `[Name, NameBuiltin,
+    /// NameBuiltinPseudo]`.
+    pub fn split(&self) -> Vec<TokenType> {
+        let mut result = self.ancestors().collect::<Vec<_>>();
+        result.reverse();
+        result
+    }
+
+    /// Return the full name Pygments uses for this type, such as
+    /// `Token.Literal.String.Single`.
+    ///
+    /// Pygments places strings and numbers under `Literal`, and whitespace
+    /// under `Text`.
+    pub fn as_pygments_str(&self) -> ::std::string::String {
+        let name = self.as_str();
+        for &(ours, theirs) in PYGMENTS_RENAMES {
+            if let Some(rest) = strip_type_prefix(name, ours) {
+                return format!("Token.{}{}", theirs, rest);
+            }
+        }
+        format!("Token.{}", name)
+    }
+
+    /// Return the type for a full Pygments name like `Token.Name.Tag`, or
+    /// `None` if the name is malformed or unknown.
+    ///
+    /// Like `from_existing_str`, this doesn't create custom types; the root
+    /// `Token` becomes `Text`.
+    pub fn from_pygments_str(s: &str) -> Option<Self> {
+        if s == "Token" {
+            return Some(Text);
+        }
+        if !s.starts_with("Token.") || !s[6..].split('.').all(
+            |part| part.chars().next().map_or(false, |c| c.is_uppercase())) {
+            return None;
+        }
+        let name = &s[6..];
+        for &(ours, theirs) in PYGMENTS_RENAMES {
+            if let Some(rest) = strip_type_prefix(name, theirs) {
+                return TokenType::from_existing_str(&format!("{}{}", ours, rest));
+            }
+        }
+        TokenType::from_existing_str(name)
+    }
+}
+
+// Top-level types that Pygments places elsewhere in the hierarchy.
+static PYGMENTS_RENAMES: &'static [(&'static str, &'static str)] = &[
+    ("String", "Literal.String"),
+    ("Number", "Literal.Number"),
+    ("Whitespace", "Text.Whitespace"),
+];
+
+// If `name` is the type name `prefix` or one of its subtypes, return the
+// rest of the name after the prefix.
+fn strip_type_prefix<'a>(name: &'a str, prefix: &str) -> Option<&'a str> {
+    if name.starts_with(prefix) &&
+        (name.len() == prefix.len() || name[prefix.len()..].starts_with('.')) {
+        Some(&name[prefix.len()..])
+    } else {
+        None
+    }
+}
+
+/// Iterator over a token type and its ancestors, see `TokenType::ancestors`.
+pub struct Ancestors(Option<TokenType>);
+
+impl Iterator for Ancestors {
+    type Item = TokenType;
+
+    fn next(&mut self) -> Option<TokenType> {
+        self.0.map(|ttype| {
+            self.0 = ttype.parent();
+            ttype
+        })
+    }
+}
+
+// Registry of custom token types, which gives them static names and short
+// names (see `custom_short_name`).
+struct Interner {
+    // maps the name to the static name and short name
+    names: HashMap<&'static str, (&'static str, &'static str)>,
+}
+
+lazy_static! {
+    static ref CUSTOM_TYPES: RwLock<Interner> = RwLock::new(Interner { names: HashMap::new() });
+}
+
+impl Interner {
+    fn intern(&mut self, name: &str) -> (&'static str, &'static str) {
+        if let Some(&entry) = self.names.get(name) {
+            return entry;
+        }
+        self.insert(Box::leak(name.to_owned().into_boxed_str()))
+    }
+
+    fn insert(&mut self, name: &'static str) -> (&'static str, &'static str) {
+        let entry = (name, &*Box::leak(custom_short_name(name).0.into_boxed_str()));
+        self.names.insert(name, entry);
+        entry
+    }
+}
+
+// Return the short name of a custom type, registering the type if it was
+// created directly as `Custom(name)`.
+fn custom_short_str(name: &'static str) -> &'static str {
+    if let Some(&(_, short)) = CUSTOM_TYPES.read().unwrap().names.get(name) {
+        return short;
+    }
+    let mut types = CUSTOM_TYPES.write().unwrap();
+    match types.names.get(name) {
+        Some(&(_, short)) => short,
+        None => types.insert(name).1,
+    }
+}
+
+// Return the short name of the custom type `name`, and whether it is a
+// plain one.
+//
+// The short name depends on the name alone, not on which other types exist.
+// A name made of a standard type (with a short name of its own) and
+// alphanumeric parts gets a plain short name: the short name of the
+// standard type plus the parts, so "Name.Builtin.Jinja" becomes "nb-Jinja".
+// Since neither these nor the standard short names contain underscores,
+// any other name is kept apart from them by appending an underscore and a
+// hash of the full name.
+fn custom_short_name(name: &str) -> (::std::string::String, bool) {
+    let (parent, last) = match name.rfind('.') {
+        Some(idx) => (Some(&name[..idx]), &name[idx + 1..]),
+        None => (None, name),
+    };
+    let (parent_short, parent_plain) = match parent {
+        Some(parent) => match TokenType::standard_from_str(parent) {
+            Some(parent) => {
+                let short = parent.as_short_str();
+                let shared = STANDARD_TYPES.iter().filter(|t| t.as_short_str() == short).count();
+                (short.to_owned(), !short.is_empty() && shared == 1)
+            }
+            None => custom_short_name(parent),
+        },
+        None => (::std::string::String::new(), false),
+    };
+    let plain = parent_plain && !last.is_empty() && last.chars().all(char::is_alphanumeric);
+    let last = last.chars().map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+                           .collect::<::std::string::String>();
+    let base = if parent_short.is_empty() {
+        last
+    } else {
+        format!("{}-{}", parent_short, last)
+    };
+    if plain {
+        (base, true)
+    } else {
+        (format!("{}_{:08x}", base, fnv1a(name)), false)
+    }
+}
+
+// 32-bit FNV-1a hash, which unlike the std hashers is fixed for all time.
+fn fnv1a(s: &str) -> u32 {
+    s.bytes().fold(0x811c9dc5, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193))
+}
+
+/// With the `serde` feature, token types are serialized as their dotted
+/// names; only standard and already created custom types can be read back.
+/// Tokens are serialized as `{"type": ..., "text": ..., "offset": ...}`
+/// maps, like the objects written by the `JsonFormatter`; the offset is
+/// left out for tokens without one.
+#[cfg(feature = "serde")]
+mod serde_impls {
+    use std::borrow::Cow;
+    use std::fmt;
+    use serde::{Serialize, Serializer, Deserialize, Deserializer};
+    use serde::de::{self, Visitor, MapAccess};
+    use serde::ser::SerializeStruct;
+    use super::{Token, TokenType};
+
+    impl Serialize for TokenType {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_str(self.as_str())
+        }
+    }
+
+    struct TokenTypeVisitor;
+
+    impl<'de> Visitor<'de> for TokenTypeVisitor {
+        type Value = TokenType;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a token type name")
+        }
+
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<TokenType, E> {
+            TokenType::from_existing_str(v).ok_or_else(
+                || E::custom(format!("unknown token type {:?}", v)))
+        }
+    }
+
+    impl<'de> Deserialize<'de> for TokenType {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_str(TokenTypeVisitor)
+        }
+    }
+
+    impl<'t> Serialize for Token<'t> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let len = if self.offset.is_some() { 3 } else { 2 };
+            let mut st = try!(serializer.serialize_struct("Token", len));
+            try!(st.serialize_field("type", &self.ttype));
+            try!(st.serialize_field("text", &*self.text));
+            if let Some(offset) = self.offset {
+                try!(st.serialize_field("offset", &offset));
+            }
+            st.end()
+        }
+    }
+
+    struct TokenVisitor;
+
+    impl<'de> Visitor<'de> for TokenVisitor {
+        type Value = Token<'static>;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            f.write_str("a token map")
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+            let mut ttype = None;
+            let mut text: Option<::std::string::String> = None;
+            let mut offset = None;
+            while let Some(key) = try!(map.next_key::<::std::string::String>()) {
+                match &*key {
+                    "type" => ttype = Some(try!(map.next_value())),
+                    "text" => text = Some(try!(map.next_value())),
+                    "offset" => offset = try!(map.next_value()),
+                    _ => { try!(map.next_value::<de::IgnoredAny>()); }
+                }
+            }
+            let ttype = try!(ttype.ok_or_else(|| de::Error::missing_field("type")));
+            let text = try!(text.ok_or_else(|| de::Error::missing_field("text")));
+            let mut tok = Token::new(Cow::Owned(text), ttype);
+            tok.offset = offset;
+            Ok(tok)
+        }
+    }
+
+    /// Deserialized tokens always own their text.
+    impl<'de, 't> Deserialize<'de> for Token<'t> {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_struct("Token", &["type", "text", "offset"],
+                                            TokenVisitor)
         }
     }
 }
@@ -0,0 +1,163 @@
+// Copyright (c) 2006-2015 by the respective authors (see AUTHORS file).
+// All rights reserved.
+//
+// Redistribution and use in source and binary forms, with or without
+// modification, are permitted provided that the following conditions are
+// met:
+//
+// * Redistributions of source code must retain the above copyright
+//   notice, this list of conditions and the following disclaimer.
+//
+// * Redistributions in binary form must reproduce the above copyright
+//   notice, this list of conditions and the following disclaimer in the
+//   documentation and/or other materials provided with the distribution.
+//
+// THIS SOFTWARE IS PROVIDED BY THE COPYRIGHT HOLDERS AND CONTRIBUTORS
+// "AS IS" AND ANY EXPRESS OR IMPLIED WARRANTIES, INCLUDING, BUT NOT
+// LIMITED TO, THE IMPLIED WARRANTIES OF MERCHANTABILITY AND FITNESS FOR
+// A PARTICULAR PURPOSE ARE DISCLAIMED. IN NO EVENT SHALL THE COPYRIGHT
+// OWNER OR CONTRIBUTORS BE LIABLE FOR ANY DIRECT, INDIRECT, INCIDENTAL,
+// SPECIAL, EXEMPLARY, OR CONSEQUENTIAL DAMAGES (INCLUDING, BUT NOT
+// LIMITED TO, PROCUREMENT OF SUBSTITUTE GOODS OR SERVICES; LOSS OF USE,
+// DATA, OR PROFITS; OR BUSINESS INTERRUPTION) HOWEVER CAUSED AND ON ANY
+// THEORY OF LIABILITY, WHETHER IN CONTRACT, STRICT LIABILITY, OR TORT
+// (INCLUDING NEGLIGENCE OR OTHERWISE) ARISING IN ANY WAY OUT OF THE USE
+// OF THIS SOFTWARE, EVEN IF ADVISED OF THE POSSIBILITY OF SUCH DAMAGE.
+
+//! Utility functions.
+
+use std::cmp;
+
+use regex::Regex;
+
+/// Match a file name against a shell-style glob pattern.
+///
+/// Supported are `*`, `?`, and character classes like `[a-z]` and `[!0-9]`.
+pub fn fnmatch(pattern: &str, name: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let name = name.chars().collect::<Vec<_>>();
+    fnmatch_chars(&pattern, &name)
+}
+
+fn fnmatch_chars(pattern: &[char], name: &[char]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(&'*') => (0..name.len() + 1).any(|i| fnmatch_chars(&pattern[1..], &name[i..])),
+        Some(&'?') => !name.is_empty() && fnmatch_chars(&pattern[1..], &name[1..]),
+        Some(&'[') => {
+            // a "]" directly after the opening bracket is part of the class
+            let negate = pattern.get(1) == Some(&'!');
+            let start = if negate { 2 } else { 1 };
+            let end = match pattern.iter().skip(start + 1).position(|&c| c == ']') {
+                Some(idx) => start + 1 + idx,
+                None => return name.first() == Some(&'[') &&
+                               fnmatch_chars(&pattern[1..], &name[1..]),
+            };
+            let ch = match name.first() {
+                Some(&ch) => ch,
+                None => return false,
+            };
+            let class = &pattern[start..end];
+            let mut found = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    found |= class[i] <= ch && ch <= class[i + 2];
+                    i += 3;
+                } else {
+                    found |= class[i] == ch;
+                    i += 1;
+                }
+            }
+            found != negate && fnmatch_chars(&pattern[end + 1..], &name[1..])
+        }
+        Some(&c) => name.first() == Some(&c) && fnmatch_chars(&pattern[1..], &name[1..]),
+    }
+}
+
+lazy_static! {
+    static ref DOCTYPE_LOOKUP_RE: Regex = Regex::new(
+        r#"(?s)\A(?:<\?.*?\?>)?\s*<!(?i:doctype)\s+([a-zA-Z_][a-zA-Z0-9]*(?:\s+[a-zA-Z_][a-zA-Z0-9]*\s+"[^"]*")?)[^>]*>"#
+    ).unwrap();
+    static ref VIM_MODELINE_RE: Regex = Regex::new(
+        r"(?:vi|vim|ex)(?:[<=>]?\d*)?:.*?(?:ft|filetype|syn|syntax)=([^:\s]+)"
+    ).unwrap();
+    static ref EMACS_MODELINE_RE: Regex = Regex::new(r"-\*-(.*?)-\*-").unwrap();
+}
+
+/// Check if the first line of `text` is a shebang whose interpreter matches
+/// `regex`.
+///
+/// The regex is matched case-insensitively against the last path component
+/// of the interpreter, ignoring options, so `#!/usr/bin/env python -u`
+/// matches `python` or `python[23]?`.
+pub fn shebang_matches(text: &str, regex: &str) -> bool {
+    let first_line = text.lines().next().unwrap_or("").to_lowercase();
+    if !first_line.starts_with("#!") {
+        return false;
+    }
+    let found = match first_line[2..].split(|c| c == '/' || c == '\\' || c == ' ')
+                                     .filter(|x| !x.is_empty() && !x.starts_with('-'))
+                                     .last() {
+        Some(found) => found,
+        None => return false,
+    };
+    match Regex::new(&format!(r"(?i)\A(?:{})(?:\.(?:exe|cmd|bat|bin))?\z", regex)) {
+        Ok(rx) => rx.is_match(found),
+        Err(_) => false,
+    }
+}
+
+/// Check if `text` starts with a doctype declaration whose document type
+/// matches `regex` (case-insensitively).
+pub fn doctype_matches(text: &str, regex: &str) -> bool {
+    let doctype = match DOCTYPE_LOOKUP_RE.captures(text).and_then(|cap| cap.at(1)) {
+        Some(doctype) => doctype.trim(),
+        None => return false,
+    };
+    match Regex::new(&format!(r"(?i)\A(?:{})", regex)) {
+        Ok(rx) => rx.is_match(doctype),
+        Err(_) => false,
+    }
+}
+
+/// Check if `text` starts with an HTML doctype declaration.
+pub fn html_doctype_matches(text: &str) -> bool {
+    doctype_matches(text, "html")
+}
+
+/// Look for an Emacs or Vim modeline in the first and last five lines of
+/// `text` and return the file type it declares.
+///
+/// Recognized are `vim: set ft=python:`-style Vim modelines and both the
+/// `-*- mode: python -*-` and the short `-*- python -*-` form of Emacs.
+pub fn get_filetype_from_buffer(text: &str) -> Option<String> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let tail_start = cmp::min(lines.len(), cmp::max(5, lines.len().saturating_sub(5)));
+    for line in lines[tail_start..].iter().rev().chain(lines.iter().take(5)) {
+        if let Some(ft) = get_filetype_from_line(line) {
+            return Some(ft);
+        }
+    }
+    None
+}
+
+fn get_filetype_from_line(line: &str) -> Option<String> {
+    if let Some(cap) = VIM_MODELINE_RE.captures(line) {
+        return cap.at(1).map(|ft| ft.to_owned());
+    }
+    if let Some(vars) = EMACS_MODELINE_RE.captures(line).and_then(|cap| cap.at(1)) {
+        if !vars.contains(':') {
+            let mode = vars.trim();
+            return if mode.is_empty() { None } else { Some(mode.to_owned()) };
+        }
+        for var in vars.split(';') {
+            let mut parts = var.splitn(2, ':');
+            let key = parts.next().unwrap_or("").trim();
+            if key.eq_ignore_ascii_case("mode") {
+                return parts.next().map(|mode| mode.trim().to_owned());
+            }
+        }
+    }
+    None
+}
@@ -1,16 +1,811 @@
-use lexers::html::HtmlLexer;
+use std::env;
+use std::fs::{self, File};
+use std::io::{Read, Write};
+
+use filter::Filter;
+use filters::{Case, CodeTagFilter, KeywordCaseFilter, NameHighlightFilter,
+              RaiseOnErrorTokenFilter, VisibleWhitespaceFilter};
+use formatter::Formatter;
+use formatters::{find_formatter_by_name, find_formatter_for_filename, get_formatter_by_name,
+                 get_formatter_for_filename};
+use formatters::html::{HtmlFormatter, LinenosMode};
+use formatters::json::{escape_json, JsonFormatter};
+use formatters::latex::{escape_tex, LatexFormatter};
+use formatters::other::RawTokenFormatter;
+use formatters::rtf::RtfFormatter;
+use formatters::svg::SvgFormatter;
+use formatters::terminal::TerminalFormatter;
+use formatters::terminal256::{closest_xterm_color, Terminal256Formatter,
+                               TerminalTrueColorFormatter};
+use lexer::{Lexer, Machine, MachineErrorKind, MatchAction, RegexLexer, StateAction};
+use lexers::css::CSS_TOKEN_DEF;
+use lexers::html::{HtmlLexer, HTML_TOKEN_DEF};
+use lexers::javascript::{JavascriptLexer, JAVASCRIPT_LEXER, JAVASCRIPT_TOKEN_DEF};
+use lexers::special::{RawTokenLexer, TextLexer, TEXT_TOKEN_DEF};
+use lexers::{find_lexer_by_name, find_lexer_for_filename, find_lexer_for_mimetype,
+             get_lexer_for_filename, guess_lexer_info, guess_lexer_info_for_filename};
+use macros::RuleDef;
+use regexopt::{regex_opt, regex_opt_capturing};
+use style::{Color, Style, StyleDef, TokenStyle};
+use token::{Position, Token, TokenType, STANDARD_TYPES};
+use util::{fnmatch, get_filetype_from_buffer, html_doctype_matches, shebang_matches};
+use {highlight, highlight_to_string, Highlighted};
 static TESTHTML: &'static [u8; 5815] = include_bytes!("input/test.html");
 
+define_machine! {
+    WORDS_MACHINE,
+    WORDS_TOKEN_DEF,
+    [],
+    "root": [
+        (words(("if", "in", "int", "else"), suffix = r"\b"), Keyword),
+        (r"\w+", Name),
+        (r"\s+", Text),
+    ],
+}
+
+define_machine! {
+    BASE_MACHINE,
+    BASE_TOKEN_DEF,
+    [],
+    "root": [
+        include("whitespace"),
+        (r"\w+", Name),
+    ],
+    "whitespace": [
+        (r"\s+", Text),
+    ],
+}
+
+define_machine! {
+    DERIVED_MACHINE,
+    DERIVED_TOKEN_DEF,
+    [],
+    inherit BASE_MACHINE,
+    "root": [
+        (r"#[^\n]*", Comment),
+        inherit,
+    ],
+    "whitespace": [
+        (r"\s+", Whitespace),
+    ],
+}
+
+fn pairs<'a>(toks: &'a [Token]) -> Vec<(&'a str, TokenType)> {
+    toks.iter().map(|tok| (&*tok.text, tok.ttype)).collect()
+}
+
 #[test]
 fn check_html() {
     let bufstr = String::from_utf8_lossy(TESTHTML).into_owned();
     let mut result = String::with_capacity(bufstr.len());
     for tok in HtmlLexer::new(&bufstr) {
-        result.push_str(tok.text);
+        result.push_str(&tok.text);
     }
     assert_eq!(bufstr, result);
 }
 
+#[test]
+fn check_regex_opt() {
+    assert_eq!(regex_opt(&["int", "if", "in"], "", ""), "(?:i(?:nt|[fn]))");
+    assert_eq!(regex_opt(&["a-", "b-", "-"], r"\b", ""), r"\b(?:(?:(?:[ab])?)-)");
+    assert_eq!(regex_opt_capturing(&["int", "if", "in"], "", ""), "(i(?:nt|[fn]))");
+}
+
+#[test]
+fn check_words() {
+    let result = RegexLexer::new(&WORDS_MACHINE, "root", "in into int").collect::<Vec<_>>();
+    assert_eq!(pairs(&result), vec![("in", TokenType::Keyword), (" ", TokenType::Text),
+                                   ("into", TokenType::Name), (" ", TokenType::Text),
+                                   ("int", TokenType::Keyword)]);
+}
+
+#[test]
+fn check_using() {
+    let text = "<script>var x = '</p>';</script><style>p { color: red }</style>";
+    let mut result = String::new();
+    let mut types = Vec::new();
+    for tok in HtmlLexer::new(text) {
+        result.push_str(&tok.text);
+        types.push((tok.text.into_owned(), tok.ttype));
+    }
+    assert_eq!(result, text);
+    assert!(types.contains(&("var".into(), TokenType::KeywordDeclaration)));
+    assert!(types.contains(&("'</p>'".into(), TokenType::StringSingle)));
+    assert!(types.contains(&("color".into(), TokenType::Keyword)));
+    assert!(types.contains(&("red".into(), TokenType::Name)));
+    assert_eq!(types.last(), Some(&(">".into(), TokenType::Punctuation)));
+}
+
+#[test]
+fn check_empty_matches() {
+    let result = JavascriptLexer::new("var x = y").collect::<Vec<_>>();
+    assert_eq!(pairs(&result), vec![("var", TokenType::KeywordDeclaration),
+                                   (" ", TokenType::Text), ("x", TokenType::NameOther),
+                                   (" ", TokenType::Text), ("=", TokenType::Operator),
+                                   (" ", TokenType::Text), ("y", TokenType::NameOther)]);
+    let result = HtmlLexer::new("<p a=1></p>").collect::<Vec<_>>();
+    assert!(result.iter().all(|tok| !tok.text.is_empty()));
+}
+
+#[test]
+fn check_machines() {
+    for &def in &[HTML_TOKEN_DEF, CSS_TOKEN_DEF, JAVASCRIPT_TOKEN_DEF, TEXT_TOKEN_DEF] {
+        if let Err(err) = Machine::try_convert(def) {
+            panic!("{}", err);
+        }
+    }
+}
+
+#[test]
+fn check_machine_errors() {
+    const TEXT: MatchAction = MatchAction::Single(TokenType::Text);
+    match Machine::try_convert(&[("root", &[RuleDef::Regex("a", TEXT, StateAction::None),
+                                            RuleDef::Regex("(", TEXT, StateAction::None)])]) {
+        Err(err) => {
+            assert_eq!((err.state, err.rule), ("root", Some(1)));
+            match err.kind { MachineErrorKind::InvalidRegex(_) => {}, _ => panic!("{}", err) }
+        }
+        Ok(_) => panic!("invalid regex not detected"),
+    }
+    match Machine::try_convert(&[("root", &[RuleDef::Include("other")]),
+                                 ("other", &[RuleDef::Regex("a", TEXT, StateAction::Push("stat"))])]) {
+        Err(err) => {
+            assert_eq!((err.state, err.rule), ("other", Some(0)));
+            match err.kind { MachineErrorKind::UnknownState("stat") => {}, _ => panic!("{}", err) }
+        }
+        Ok(_) => panic!("unknown state not detected"),
+    }
+    match Machine::try_convert(&[("main", &[RuleDef::Default(StateAction::None)])]) {
+        Err(err) => {
+            match err.kind { MachineErrorKind::MissingRoot => {}, _ => panic!("{}", err) }
+        }
+        Ok(_) => panic!("missing root not detected"),
+    }
+    match Machine::try_convert(&[("root", &[RuleDef::Regex("a", TEXT, StateAction::Push("a"))]),
+                                 ("a", &[RuleDef::Regex("b", TEXT, StateAction::PopMulti(2))])]) {
+        Err(err) => {
+            assert_eq!((err.state, err.rule), ("a", Some(0)));
+            match err.kind { MachineErrorKind::PopBelowBottom("a") => {}, _ => panic!("{}", err) }
+        }
+        Ok(_) => panic!("pop below bottom not detected"),
+    }
+    match Machine::try_convert(&[("root", &[RuleDef::Regex("a", TEXT, StateAction::Push("a"))]),
+                                 ("a", &[RuleDef::Default(StateAction::Push("#pop:x"))])]) {
+        Err(err) => {
+            assert_eq!((err.state, err.rule), ("a", Some(0)));
+            match err.kind {
+                MachineErrorKind::InvalidStateAction("#pop:x") => {},
+                _ => panic!("{}", err)
+            }
+        }
+        Ok(_) => panic!("invalid state action not detected"),
+    }
+    assert!(Machine::try_convert(&[("root", &[RuleDef::Regex("a", TEXT, StateAction::Push("a"))]),
+                                   ("a", &[RuleDef::Regex("a", TEXT, StateAction::Push("b"))]),
+                                   ("b", &[RuleDef::Default(StateAction::Push("#pop:2"))])])
+            .is_ok());
+}
+
+define_machine! {
+    ANCHOR_MACHINE, ANCHOR_TOKEN_DEF, [],
+    "root": [
+        (r"a|b", Keyword),
+        (words(("if", "in")), Keyword),
+        (r".", Text)
+    ]
+}
+
+#[test]
+fn check_rule_anchoring() {
+    // every alternative of a rule must only match at the current position
+    let toks: Vec<_> = RegexLexer::new(&ANCHOR_MACHINE, "root", "cbxin").collect();
+    assert_eq!(pairs(&toks), [("c", TokenType::Text), ("b", TokenType::Keyword),
+                              ("x", TokenType::Text), ("in", TokenType::Keyword)]);
+}
+
+define_machine! {
+    GAP_MACHINE, GAP_TOKEN_DEF, [],
+    "root": [
+        (r"(a)-(b)", bygroups(Keyword, Name)),
+        (r"\s+", Text)
+    ]
+}
+
+#[test]
+fn check_positions() {
+    let text = "<p>\n  <b>\u{e4}</b></p>";
+    for (i, tok) in HtmlLexer::new(text).indexed() {
+        assert_eq!(&text[i..i + tok.text.len()], tok.text);
+    }
+    let positions = HtmlLexer::new(text).positioned().collect::<Vec<_>>();
+    assert_eq!(positions[4].0, Position { offset: 6, line: 2, column: 3 });
+    assert_eq!(positions[4].1.text, "<");
+    assert_eq!(positions[8].0, Position { offset: 11, line: 2, column: 7 });
+    assert_eq!(positions[8].1.text, "<");
+    // offsets of delegated tokens and of tokens split by filters
+    let text = "<p>x</p><script>\n  f(); // TODO: \u{e4}\n</script>";
+    let lexer = HtmlLexer::new(text).with_filter(CodeTagFilter::new());
+    for (i, tok) in lexer.indexed() {
+        assert_eq!(&text[i..i + tok.text.len()], tok.text);
+    }
+    // text outside of groups is skipped, but doesn't shift the offsets
+    let offsets = RegexLexer::new(&GAP_MACHINE, "root", "a-b a-b").indexed()
+        .map(|(i, tok)| (i, tok.text.into_owned())).collect::<Vec<_>>();
+    assert_eq!(offsets, [(0, "a".into()), (2, "b".into()), (3, " ".into()),
+                         (4, "a".into()), (6, "b".into())]);
+    // lines and columns come from the source, not the token texts
+    let positions = RegexLexer::new(&GAP_MACHINE, "root", "a-b\na-b").positioned()
+        .map(|(pos, _)| pos).collect::<Vec<_>>();
+    assert_eq!(positions[4], Position { offset: 6, line: 2, column: 3 });
+    let positions = JavascriptLexer::new("\tvar x\n\tvar y")
+        .with_filter(VisibleWhitespaceFilter::new().with_tabsize(4))
+        .positioned().filter(|&(_, ref tok)| tok.text == "var")
+        .map(|(pos, _)| pos).collect::<Vec<_>>();
+    assert_eq!(positions, [Position { offset: 1, line: 1, column: 2 },
+                           Position { offset: 8, line: 2, column: 2 }]);
+}
+
+#[test]
+fn check_registry() {
+    assert!(fnmatch("*.htm[l!]", "index.html"));
+    assert!(fnmatch("[!.]*.?s", "main.js"));
+    assert!(!fnmatch("[!.]*.?s", ".js"));
+    assert!(!fnmatch("*.js", "main.json"));
+    assert_eq!(find_lexer_by_name("JS").map(|info| info.name), Some("JavaScript"));
+    assert_eq!(find_lexer_for_filename("/srv/www/index.htm").map(|info| info.name),
+               Some("HTML"));
+    assert_eq!(find_lexer_for_mimetype("text/css").map(|info| info.name), Some("CSS"));
+    assert!(find_lexer_for_filename("Makefile").is_none());
+    let lexer = get_lexer_for_filename("style.css", "p { }").unwrap();
+    assert_eq!(lexer.map(|tok| tok.ttype).collect::<Vec<_>>(),
+               vec![TokenType::NameTag, TokenType::Text, TokenType::Punctuation,
+                    TokenType::Text, TokenType::Punctuation]);
+}
+
+#[test]
+fn check_formatter_registry() {
+    for &(name, expected) in &[("html", "HTML"), ("SVG", "SVG"), ("tex", "LaTeX"),
+                               ("ansi", "Terminal")] {
+        assert_eq!(find_formatter_by_name(name).map(|info| info.name), Some(expected));
+    }
+    assert_eq!(find_formatter_for_filename("out/page.htm").map(|info| info.name),
+               Some("HTML"));
+    assert_eq!(find_formatter_for_filename("doc.tex").map(|info| info.name), Some("LaTeX"));
+    assert!(find_formatter_by_name("pdf").is_none());
+
+    let mut formatters = vec![get_formatter_by_name("json").unwrap(),
+                              get_formatter_for_filename("x.raw").unwrap()];
+    let mut out = Vec::new();
+    for fmt in &mut formatters {
+        fmt.format(JavascriptLexer::new("x"), &mut out).unwrap();
+    }
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "[{\"type\":\"Name.Other\",\"text\":\"x\",\"offset\":0}]Token.Name.Other\t'x'\n");
+    let fmt = get_formatter_by_name("html").unwrap();
+    assert_eq!(fmt.get_stylesheet(".x"), HtmlFormatter::new().get_stylesheet(".x"));
+}
+
+#[test]
+fn check_guessing() {
+    assert!(shebang_matches("#!/usr/bin/env node --harmony\n", r"node(?:js)?"));
+    assert!(shebang_matches("#!C:\\node\\NODE.EXE", "node"));
+    assert!(!shebang_matches("#!/usr/bin/nodes", "node"));
+    assert!(html_doctype_matches("<?xml version=\"1.0\"?>\n<!DOCTYPE html PUBLIC \"x\">"));
+    assert!(html_doctype_matches("<!doctype html><p>"));
+    assert!(!html_doctype_matches("<p><!DOCTYPE html>"));
+    assert_eq!(get_filetype_from_buffer("x\n/* vim: set ts=4 ft=css: */\n"),
+               Some("css".into()));
+    assert_eq!(get_filetype_from_buffer("// -*- mode: js; tab-width: 4 -*-"),
+               Some("js".into()));
+    assert_eq!(get_filetype_from_buffer("/* -*- css -*- */"), Some("css".into()));
+    assert_eq!(get_filetype_from_buffer("# -*- coding: utf-8 -*-"), None);
+
+    assert_eq!(guess_lexer_info("<!DOCTYPE html>\n<html></html>").name, "HTML");
+    assert_eq!(guess_lexer_info("#!/usr/bin/node\nvar x;").name, "JavaScript");
+    assert_eq!(guess_lexer_info("p { } /* vim: ft=css */").name, "CSS");
+    assert_eq!(guess_lexer_info("just some words").name, "Text only");
+    assert_eq!(guess_lexer_info_for_filename("notes.txt", "<!DOCTYPE html>").map(|i| i.name),
+               Some("Text only"));
+    assert_eq!(guess_lexer_info_for_filename("x.html", "// -*- js -*-").map(|i| i.name),
+               Some("JavaScript"));
+    assert!(guess_lexer_info_for_filename("Makefile", "all:").is_none());
+}
+
+#[test]
+fn check_filters() {
+    let text = "var x = y; // TODO: fix\n";
+    let result = JavascriptLexer::new(text)
+        .with_filter(KeywordCaseFilter::new(Case::Upper))
+        .with_filter(NameHighlightFilter::new(&["y"]))
+        .with_filter(CodeTagFilter::new())
+        .collect::<Vec<_>>();
+    let result = pairs(&result);
+    assert_eq!(result[0], ("VAR", TokenType::KeywordDeclaration));
+    assert!(result.contains(&("x", TokenType::NameOther)));
+    assert!(result.contains(&("y", TokenType::NameBuiltin)));
+    assert!(result.contains(&("TODO", TokenType::CommentSpecial)));
+    assert!(result.contains(&(": fix", TokenType::CommentSingle)));
+    let result = JavascriptLexer::new(text)
+        .with_filter(CodeTagFilter::with_codetags(&[]))
+        .collect::<Vec<_>>();
+    assert!(pairs(&result).contains(&("// TODO: fix", TokenType::CommentSingle)));
+
+    let result = JavascriptLexer::new("a\tb c\n")
+        .with_filter(VisibleWhitespaceFilter::new().with_tabsize(2))
+        .map(|tok| tok.text.into_owned()).collect::<String>();
+    assert_eq!(result, "a» b·c¶\n");
+    let result = JavascriptLexer::new("a b")
+        .with_filter(VisibleWhitespaceFilter::new().with_wstokentype(false))
+        .collect::<Vec<_>>();
+    assert_eq!(pairs(&result), vec![("a", TokenType::NameOther), ("·", TokenType::Text),
+                                    ("b", TokenType::NameOther)]);
+}
+
+#[test]
+#[should_panic(expected = "error token")]
+fn check_raise_on_error() {
+    for _ in JavascriptLexer::new("\u{1}").with_filter(RaiseOnErrorTokenFilter) { }
+}
+
+#[test]
+fn check_error_tokens() {
+    let result = RaiseOnErrorTokenFilter::check(JavascriptLexer::new("a"))
+        .collect::<Result<Vec<_>, _>>().unwrap();
+    assert_eq!(pairs(&result), vec![("a", TokenType::NameOther)]);
+    let mut checked = RaiseOnErrorTokenFilter::check(JavascriptLexer::new("a \u{1} \u{1}"));
+    assert!(checked.next().unwrap().is_ok());
+    assert!(checked.next().unwrap().is_ok());
+    let err = checked.next().unwrap().unwrap_err();
+    assert_eq!((&*err.text, err.offset), ("\u{1}", Some(2)));
+    assert_eq!(err.to_string(), "lexer produced an error token: \"\\u{1}\" at offset 2");
+    assert!(checked.next().is_none());
+}
+
+#[test]
+fn check_token_hierarchy() {
+    assert_eq!(TokenType::NameBuiltinPseudo.parent(), Some(TokenType::NameBuiltin));
+    assert_eq!(TokenType::Name.parent(), None);
+    for ttype in STANDARD_TYPES {
+        let name = ttype.as_str();
+        assert_eq!(ttype.parent(), name.rfind('.').map(|idx| TokenType::from_str(&name[..idx])));
+    }
+    assert_eq!(TokenType::Custom("Comment.Single.Doc").parent(), Some(TokenType::CommentSingle));
+    assert_eq!(TokenType::NumberIntegerLong.ancestors().collect::<Vec<_>>(),
+               vec![TokenType::NumberIntegerLong, TokenType::NumberInteger, TokenType::Number]);
+    assert_eq!(TokenType::StringDoc.split(), vec![TokenType::String, TokenType::StringDoc]);
+    assert!(TokenType::CommentSingle.is_subtype_of(TokenType::Comment));
+    assert!(TokenType::Comment.is_subtype_of(TokenType::Comment));
+    assert!(!TokenType::Comment.is_subtype_of(TokenType::CommentSingle));
+    assert!(!TokenType::Keyword.is_subtype_of(TokenType::Name));
+}
+
+#[test]
+fn check_custom_types() {
+    let name = String::from("Name.Builtin.Jinja");
+    let jinja = TokenType::from_str(&name);
+    assert_eq!(jinja, TokenType::NameBuiltin.subtype("Jinja"));
+    assert_eq!(jinja.as_str(), "Name.Builtin.Jinja");
+    assert_eq!(jinja.as_short_str(), "nb-Jinja");
+    assert!(jinja.is_subtype_of(TokenType::Name));
+    let filter = jinja.subtype("Filter name");
+    assert_eq!(filter.parent(), Some(jinja));
+    assert_eq!(filter.as_short_str(), "nb-Jinja-Filter_name_1654f19a");
+    assert_eq!(TokenType::from_str("Keyword.Type"), TokenType::KeywordType);
+    // short names don't clash with the standard ones
+    assert_eq!(TokenType::from_str("k").as_short_str(), "k_ee0c38ea");
+    assert_eq!(TokenType::Custom("Comment.Todo").as_short_str(), "c-Todo");
+    assert_eq!(TokenType::Custom("Text.Foo").as_short_str(), "Foo_28ebb908");
+    // and are the same in all threads
+    let ttype = TokenType::Custom("Name.Tag.Threaded x");
+    let threads = (0..2).map(|_| ::std::thread::spawn(move || {
+        let mut out = Vec::new();
+        HtmlFormatter::new().with_nowrap(true)
+            .format(vec![Token::new("a", ttype)].into_iter(), &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    })).collect::<Vec<_>>();
+    let outputs = threads.into_iter().map(|t| t.join().unwrap()).collect::<Vec<_>>();
+    assert_eq!(outputs[0], "<span class=\"nt-Threaded_x_f41f2f07\">a</span>");
+    assert_eq!(outputs[0], outputs[1]);
+    // only `from_str` creates custom types
+    assert_eq!(TokenType::from_existing_str("Name.Builtin.Jinja"), Some(jinja));
+    assert_eq!(TokenType::from_existing_str("Name.Never.Created"), None);
+}
+
+static TEST_STYLE: StyleDef = StyleDef {
+    name: "test",
+    token_style: "#111",
+    background_color: "",
+    highlight_color: "",
+    styles: &[
+        (TokenType::Name, "bold #00f"),
+        (TokenType::NameBuiltin, "noinherit underline"),
+        (TokenType::NameFunction, "nobold bg:#eee"),
+    ],
+};
+
+#[test]
+fn check_style() {
+    let style = Style::from_def(&TEST_STYLE);
+    assert_eq!(*style.style_for(TokenType::Text),
+               TokenStyle { color: Some(Color(0x11, 0x11, 0x11)), .. TokenStyle::default() });
+    assert_eq!(*style.style_for(TokenType::NameTag),
+               TokenStyle { color: Some(Color(0, 0, 0xff)), bold: true,
+                            .. TokenStyle::default() });
+    assert_eq!(*style.style_for(TokenType::NameBuiltinPseudo),
+               TokenStyle { color: Some(Color(0x11, 0x11, 0x11)), underline: true,
+                            .. TokenStyle::default() });
+    assert_eq!(*style.style_for(TokenType::NameFunctionMagic),
+               TokenStyle { color: Some(Color(0, 0, 0xff)), bgcolor: Some(Color(0xee, 0xee, 0xee)),
+                            .. TokenStyle::default() });
+    let err = Style::try_from_def(&StyleDef { background_color: "#12", .. TEST_STYLE });
+    assert_eq!(err.unwrap_err().to_string(), "style test, background: invalid color \"#12\"");
+
+    let css = HtmlFormatter::new().get_stylesheet("");
+    let lines = css.lines().collect::<Vec<_>>();
+    assert_eq!(lines[0], "pre.highlight { background: #f8f8f8; }");
+    let kw = lines.iter().position(|&l| l == "pre.highlight .k { color: #008000; \
+                                               font-weight: bold } /* Keyword */");
+    let kwp = lines.iter().position(|&l| l == "pre.highlight .kp { color: #008000 } \
+                                                /* Keyword.Pseudo */");
+    assert!(kw.is_some() && kw < kwp);
+    let css = HtmlFormatter::new().with_classprefix("x-".into()).get_stylesheet(".src");
+    assert!(css.contains(".src .x-err { border: 1px solid #ff0000 } /* Error */"));
+}
+
+#[test]
+fn check_html_noclasses() {
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true)
+        .format(JavascriptLexer::new("var x = 1;"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span class=\"kd\">var</span> <span class=\"nx\">x</span> \
+                <span class=\"o\">=</span> <span class=\"mi\">1</span>\
+                <span class=\"p\">;</span>");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_noclasses(true).with_linenos(false)
+        .format(JavascriptLexer::new("var x = 1;"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<pre class=\"highlight\" style=\"background: #f8f8f8\"><span></span>\
+                <span style=\"color: #008000; font-weight: bold\">var</span> x \
+                <span style=\"color: #666666\">=</span> \
+                <span style=\"color: #666666\">1</span>;</pre>\n");
+    // changing the options of a used formatter changes the attributes
+    let mut fmt = HtmlFormatter::new().with_nowrap(true);
+    let mut out = Vec::new();
+    fmt.format(JavascriptLexer::new("var"), &mut out).unwrap();
+    let mut fmt = fmt.with_classprefix("js-".into());
+    fmt.format(JavascriptLexer::new("var"), &mut out).unwrap();
+    let mut fmt = fmt.with_noclasses(true);
+    fmt.format(JavascriptLexer::new("var"), &mut out).unwrap();
+    let mut fmt = fmt.with_style(Style::from_def(&TEST_STYLE));
+    fmt.format(JavascriptLexer::new("var"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span class=\"kd\">var</span><span class=\"js-kd\">var</span>\
+                <span style=\"color: #008000; font-weight: bold\">var</span>\
+                <span style=\"color: #111111\">var</span>");
+}
+
+#[test]
+fn check_html_hl_lines() {
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true).with_hl_lines(vec![2, 3])
+        .format(JavascriptLexer::new("x;\n/* a\nb */\ny"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span class=\"nx\">x</span><span class=\"p\">;</span>\n\
+                <span class=\"hll\"><span class=\"cm\">/* a</span>\n</span>\
+                <span class=\"hll\"><span class=\"cm\">b */</span>\n</span>\
+                <span class=\"nx\">y</span>");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true).with_noclasses(true).with_hl_lines(1..2)
+        .format(JavascriptLexer::new("x\n"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span style=\"background-color: #ffffcc\">x\n</span>");
+    let css = HtmlFormatter::new().get_stylesheet("");
+    assert!(css.contains("pre.highlight .hll { background-color: #ffffcc }\n"));
+}
+
+#[test]
+fn check_html_linenos() {
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_pre_class("".into()).with_linenostart(9).with_linenostep(2)
+        .with_linenospecial(5).with_anchorlinenos(true).with_lineanchors("L".into())
+        .format(TextLexer::new("a\nb\nc\n"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<table><tr><td class=\"linenos\"><pre>\
+                <a href=\"#L9\">  </a>\n\
+                <a href=\"#L10\"><span class=\"special\">10</span></a>\n\
+                <a href=\"#L11\">  </a>\n</pre>\n</td><td class=\"code\">\
+                <pre><span></span><a id=\"L9\"></a>a\n<a id=\"L10\"></a>b\n\
+                <a id=\"L11\"></a>c\n</pre>\n</td></tr></table>\n");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true).with_linespans("line-".into()).with_hl_lines(vec![1])
+        .format(TextLexer::new("a\nb"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span id=\"line-1\"><span class=\"hll\">a\n</span></span>\
+                <span id=\"line-2\">b</span>");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true).with_linenos_mode(LinenosMode::Inline)
+        .with_linenostart(8).with_linenospecial(10).with_hl_lines(vec![2])
+        .format(TextLexer::new("a\nb\nc"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span class=\"linenos\"> 8</span>a\n\
+                <span class=\"linenos\"> 9</span><span class=\"hll\">b\n</span>\
+                <span class=\"linenos special\">10</span>c");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_nowrap(true).with_noclasses(true)
+        .with_linenos_mode(LinenosMode::Inline).with_linenospecial(2)
+        .format(TextLexer::new("a\nb"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<span style=\"padding-right: 5px; user-select: none; -webkit-user-select: none\">\
+                1</span>a\n\
+                <span style=\"padding-right: 5px; user-select: none; -webkit-user-select: none; \
+                color: #000000; background-color: #ffffc0\">2</span>b");
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_noclasses(true).with_style(Style::from_def(&TEST_STYLE))
+        .with_linenospecial(2).format(TextLexer::new("a\nb\n"), &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().starts_with(
+        "<table class=\"highlighttable\"><tr><td class=\"linenos\"><pre>1\n\
+         <span style=\"color: #000000; background-color: #ffffc0\">2</span>\n</pre>"));
+    let css = HtmlFormatter::new().get_stylesheet("");
+    assert!(css.contains("pre.highlight .linenos { padding-right: 5px; user-select: none; "));
+    assert!(css.contains("td.linenos .special { color: #000000; background-color: #ffffc0 }"));
+}
+
+#[test]
+fn check_html_full() {
+    let mut out = Vec::new();
+    HtmlFormatter::new().with_full(true).with_title("a < b".into()).with_linenos(false)
+        .format(TextLexer::new("x\n"), &mut out).unwrap();
+    let html = String::from_utf8(out).unwrap();
+    assert!(html.starts_with(concat!("<!DOCTYPE html>\n<html>\n<head>\n",
+                                     "  <meta charset=\"utf-8\">\n",
+                                     "  <title>a &lt; b</title>\n",
+                                     "  <style>\npre.highlight { background: #f8f8f8; }\n")));
+    assert!(html.ends_with("</style>\n</head>\n<body>\n<h2>a &lt; b</h2>\n\
+                            <pre class=\"highlight\"><span></span>x\n</pre>\n\
+                            </body>\n</html>\n"));
+
+    let outdir = env::temp_dir().join("rugments-test-cssfile");
+    let cssfile = outdir.join("css").join("style.css");
+    fs::create_dir_all(cssfile.parent().unwrap()).unwrap();
+    let _ = fs::remove_file(&cssfile);
+    let mut out = Vec::new();
+    let mut fmt = HtmlFormatter::new().with_full(true).with_cssfile("css/style.css".into());
+    fmt.format(TextLexer::new("x\n"), &mut out).unwrap();
+    let html = String::from_utf8(out).unwrap();
+    assert!(html.contains("<link rel=\"stylesheet\" href=\"css/style.css\">"));
+    assert!(!html.contains("<style>"));
+    // formatting doesn't write the stylesheet; the caller has to
+    assert!(!cssfile.exists());
+    assert!(fmt.write_cssfile(&outdir).unwrap());
+    let mut css = String::new();
+    File::open(&cssfile).unwrap().read_to_string(&mut css).unwrap();
+    assert_eq!(css, fmt.get_stylesheet(""));
+    // with noclobber_cssfile, an existing file is kept
+    File::create(&cssfile).unwrap().write_all(b"/* mine */").unwrap();
+    let fmt = fmt.with_noclobber_cssfile(true);
+    assert!(!fmt.write_cssfile(&outdir).unwrap());
+    let mut css = String::new();
+    File::open(&cssfile).unwrap().read_to_string(&mut css).unwrap();
+    assert_eq!(css, "/* mine */");
+    fs::remove_dir_all(&outdir).unwrap();
+    assert!(!HtmlFormatter::new().write_cssfile(&outdir).unwrap());
+}
+
+#[test]
+fn check_terminal() {
+    let mut out = Vec::new();
+    TerminalFormatter::new().format(JavascriptLexer::new("var x /* a\nb */"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "\x1b[34mvar\x1b[39;49;00m x \x1b[37m/* a\x1b[39;49;00m\n\
+                \x1b[37mb */\x1b[39;49;00m");
+    let mut out = Vec::new();
+    TerminalFormatter::new().with_darkbg(true).with_linenos(true)
+        .format(JavascriptLexer::new("a;\nvar b = 1\n"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "0001: a;\n0002: \x1b[94mvar\x1b[39;49;00m b = \x1b[94m1\x1b[39;49;00m\n\
+                0003: \n");
+}
+
+#[test]
+fn check_terminal256() {
+    assert_eq!(closest_xterm_color(Color(0, 0x80, 0)), 28);
+    assert_eq!(closest_xterm_color(Color(0x80, 0x80, 0x80)), 244);
+    let mut out = Vec::new();
+    Terminal256Formatter::new().format(JavascriptLexer::new("var\n/*x*/"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "\x1b[38;5;28;01mvar\x1b[39;00m\n\x1b[38;5;66;03m/*x*/\x1b[39;00m");
+    let mut out = Vec::new();
+    TerminalTrueColorFormatter::new().format(JavascriptLexer::new("var"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(), "\x1b[38;2;0;128;0;01mvar\x1b[39;00m");
+}
+
+#[test]
+fn check_latex() {
+    assert_eq!(escape_tex(r"a_{\}", "PY"), r"a\PYZus{}\PYZob{}\PYZbs{}\PYZcb{}");
+    let mut out = Vec::new();
+    LatexFormatter::new().with_linenos(true).with_linenostart(10)
+        .format(JavascriptLexer::new("var x='%'"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "\\begin{Verbatim}[commandchars=\\\\\\{\\},numbers=left,firstnumber=10]\n\
+                \\PY{k+kd}{var} \\PY{n+nx}{x}\\PY{o}{=}\\PY{s+s1}{\\PYZsq{}\\PYZpc{}\\PYZsq{}}\n\
+                \\end{Verbatim}\n");
+    let defs = LatexFormatter::new().with_commandprefix("PYG".into()).get_stylesheet("");
+    assert!(defs.contains(r"\def\PYG#1#2{\PYG@reset\PYG@toks#1+\relax+\PYG@do{#2}}"));
+    assert!(defs.contains(concat!(r"\@namedef{PYG@tok@k}{\let\PYG@bf=\textbf",
+                                  r"\def\PYG@tc##1{\textcolor[rgb]{0.00,0.50,0.00}{##1}}}")));
+    assert!(defs.contains(r#"\def\PYGZdq{\char`\"}"#));
+}
+
+#[test]
+fn check_rtf() {
+    static RTF_STYLE: StyleDef = StyleDef {
+        name: "rtf",
+        token_style: "",
+        background_color: "",
+        highlight_color: "",
+        styles: &[
+            (TokenType::Keyword, "bold #f00"),
+            (TokenType::String, "bg:#00f #f00"),
+        ],
+    };
+    let mut out = Vec::new();
+    RtfFormatter::new().with_style(Style::from_def(&RTF_STYLE))
+        .with_fontface("Fira Mono".into()).with_fontsize(20)
+        .format(JavascriptLexer::new("var a = '\u{e4}\u{1f600}{'\n"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "{\\rtf1\\ansi\\uc1\\deff0{\\fonttbl{\\f0\\fmodern\\fprq1\\fcharset0 Fira Mono;}}\
+                {\\colortbl;\\red255\\green0\\blue0;\\red0\\green0\\blue255;}\\f0 \\fs20 \
+                {\\cf1\\b var} a = {\\cb2\\cf1 '\\u228?\\u-10179?\\u-8704?\\{'}\\par\n}");
+    // the font size must not run into text starting with a digit
+    let mut out = Vec::new();
+    RtfFormatter::new().with_style(Style::from_def(&RTF_STYLE)).with_fontsize(20)
+        .format(JavascriptLexer::new("1"), &mut out).unwrap();
+    assert!(String::from_utf8(out).unwrap().ends_with("\\fs20 1}"));
+}
+
+#[test]
+fn check_svg() {
+    let mut out = Vec::new();
+    SvgFormatter::new().with_nowrap(true).with_fontsize(10).with_linenos(true)
+        .format(JavascriptLexer::new("var\n\tx<y"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               "<text x=\"45\" y=\"10\" fill=\"#3d7b7b\" font-style=\"italic\" \
+                text-anchor=\"end\">1</text>\
+                <text x=\"60\" y=\"10\" xml:space=\"preserve\">\
+                <tspan fill=\"#008000\" font-weight=\"bold\">var</tspan></text>\n\
+                <text x=\"45\" y=\"25\" fill=\"#3d7b7b\" font-style=\"italic\" \
+                text-anchor=\"end\">2</text>\
+                <text x=\"60\" y=\"25\" xml:space=\"preserve\">\
+                &#160;&#160;&#160;&#160;&#160;&#160;&#160;&#160;x\
+                <tspan fill=\"#666666\">&lt;</tspan>y</text>");
+    let mut out = Vec::new();
+    SvgFormatter::new().with_fontsize(10)
+        .format(JavascriptLexer::new("ab\nc"), &mut out).unwrap();
+    let out = String::from_utf8(out).unwrap();
+    assert!(out.contains("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                          width=\"12\" height=\"30\" viewBox=\"0 0 12 30\">\n"));
+    assert!(out.ends_with("<text x=\"0\" y=\"25\" xml:space=\"preserve\">c</text></g></svg>\n"));
+    // a step of 0 shows all line numbers
+    let mut out = Vec::new();
+    SvgFormatter::new().with_nowrap(true).with_linenos(true).with_linenostep(0)
+        .format(JavascriptLexer::new("a\nb"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap().matches("text-anchor").count(), 2);
+}
+
+#[test]
+fn check_raw_tokens() {
+    assert_eq!(TokenType::StringSingle.as_pygments_str(), "Token.Literal.String.Single");
+    assert_eq!(TokenType::Whitespace.as_pygments_str(), "Token.Text.Whitespace");
+    assert_eq!(TokenType::NameTag.as_pygments_str(), "Token.Name.Tag");
+    assert_eq!(TokenType::from_pygments_str("Token.Literal.Number.Integer"),
+               Some(TokenType::NumberInteger));
+    let jinja = TokenType::NameTag.subtype("Jinja");
+    assert_eq!(TokenType::from_pygments_str("Token.Name.Tag.Jinja"), Some(jinja));
+    assert_eq!(TokenType::from_pygments_str("Token.Name.Tag.Unknown"), None);
+    assert_eq!(TokenType::from_pygments_str("Name.Tag"), None);
+    assert_eq!(TokenType::from_pygments_str("Token.name"), None);
+
+    let text = "var s = \"it's\\\t\u{e4}\u{85}\";\n";
+    let mut out = Vec::new();
+    RawTokenFormatter::new().format(JavascriptLexer::new(text), &mut out).unwrap();
+    let dump = String::from_utf8(out).unwrap();
+    assert!(dump.starts_with("Token.Keyword.Declaration\t'var'\nToken.Text\t' '\n"));
+    assert!(dump.contains(concat!("Token.Literal.String.Double\t",
+                                  r#"'"it\'s\\\tä\x85"'"#, "\n")));
+    let tokens = RawTokenLexer::new(&dump).collect::<Vec<_>>();
+    let orig = JavascriptLexer::new(text).collect::<Vec<_>>();
+    assert_eq!(pairs(&tokens), pairs(&orig));
+    let tokens = RawTokenLexer::new("Token.Text\t'a\\'\nToken.Text 'b'\n").collect::<Vec<_>>();
+    assert_eq!(pairs(&tokens), vec![("Token.Text\t'a\\'\n", TokenType::Error),
+                                    ("Token.Text 'b'\n", TokenType::Error)]);
+    let tokens = RawTokenLexer::new("Token.Name.Tag.Unknown\t'a'\n").collect::<Vec<_>>();
+    assert_eq!(pairs(&tokens), vec![("Token.Name.Tag.Unknown\t'a'\n", TokenType::Error)]);
+}
+
+#[test]
+fn check_json() {
+    assert_eq!(escape_json("a\"b\\c\n\u{1}\u{2028}\u{e4}"),
+               r#"a\"b\\c\n\u0001\u2028"#.to_owned() + "\u{e4}");
+    let mut out = Vec::new();
+    JsonFormatter::new().format(HtmlLexer::new("<p>\"\u{e4}</p>"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               concat!(r#"[{"type":"Punctuation","text":"<","offset":0},"#,
+                       r#"{"type":"Name.Tag","text":"p","offset":1},"#,
+                       r#"{"type":"Punctuation","text":">","offset":2},"#,
+                       r#"{"type":"Text","text":"\"ä","offset":3},"#,
+                       r#"{"type":"Punctuation","text":"<","offset":6},"#,
+                       r#"{"type":"Punctuation","text":"/","offset":7},"#,
+                       r#"{"type":"Name.Tag","text":"p","offset":8},"#,
+                       r#"{"type":"Punctuation","text":">","offset":9}]"#));
+    let mut out = Vec::new();
+    JsonFormatter::new().format(RegexLexer::new(&GAP_MACHINE, "root", "a-b"), &mut out).unwrap();
+    assert_eq!(String::from_utf8(out).unwrap(),
+               concat!(r#"[{"type":"Keyword","text":"a","offset":0},"#,
+                       r#"{"type":"Name","text":"b","offset":2}]"#));
+    let mut out = Vec::new();
+    JsonFormatter::new().format(HtmlLexer::new(""), &mut out).unwrap();
+    assert_eq!(out, b"[]");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn check_serde() {
+    extern crate serde_json;
+
+    let tok = Token::new("div", TokenType::NameTag);
+    let json = serde_json::to_string(&tok).unwrap();
+    assert_eq!(json, r#"{"type":"Name.Tag","text":"div"}"#);
+    let back: Token = serde_json::from_str(&json).unwrap();
+    assert_eq!(pairs(&[back]), pairs(&[tok]));
+    let tok = Token::new("div", TokenType::NameTag).with_offset(12);
+    let json = serde_json::to_string(&tok).unwrap();
+    assert_eq!(json, r#"{"type":"Name.Tag","text":"div","offset":12}"#);
+    let back: Token = serde_json::from_str(&json).unwrap();
+    assert_eq!((pairs(&[back.clone()]), back.offset), (pairs(&[tok]), Some(12)));
+    let jinja = TokenType::NameTag.subtype("Jinja");
+    let ttype: TokenType = serde_json::from_str(r#""Name.Tag.Jinja""#).unwrap();
+    assert_eq!(ttype, jinja);
+    assert!(serde_json::from_str::<TokenType>(r#""Name.Tag.Unknown""#).is_err());
+    assert!(serde_json::from_str::<Token>(r#"{"text":"x"}"#).is_err());
+}
+
+#[test]
+fn check_highlight() {
+    let code = "var x";
+    let mut out = Vec::new();
+    highlight(code, &JAVASCRIPT_LEXER, &[], &mut JsonFormatter::new(), &mut out).unwrap();
+    let expected = concat!(r#"[{"type":"Keyword.Declaration","text":"var","offset":0},"#,
+                           r#"{"type":"Text","text":" ","offset":3},"#,
+                           r#"{"type":"Name.Other","text":"x","offset":4}]"#);
+    assert_eq!(String::from_utf8(out).unwrap(), expected);
+    let filters: Vec<Box<Filter>> = vec![Box::new(KeywordCaseFilter::new(Case::Upper)),
+                                         Box::new(NameHighlightFilter::new(&["x"]))];
+    assert_eq!(highlight_to_string(code, &JAVASCRIPT_LEXER, &filters, &mut JsonFormatter::new())
+               .unwrap(),
+               concat!(r#"[{"type":"Keyword.Declaration","text":"VAR","offset":0},"#,
+                       r#"{"type":"Text","text":" ","offset":3},"#,
+                       r#"{"type":"Name.Builtin","text":"x","offset":4}]"#));
+
+    let hl = Highlighted::new(code, &JAVASCRIPT_LEXER, HtmlFormatter::new().with_nowrap(true))
+        .with_filter(KeywordCaseFilter::new(Case::Upper));
+    let html = "<span class=\"kd\">VAR</span> <span class=\"nx\">x</span>";
+    assert_eq!(format!("{}", hl), html);
+    assert_eq!(hl.to_string(), html);
+    let hl = Highlighted::new(code, &JAVASCRIPT_LEXER, get_formatter_by_name("raw").unwrap());
+    assert!(format!("<{}>", hl).starts_with("<Token.Keyword.Declaration\t'var'\n"));
+}
+
+#[test]
+fn check_inheritance() {
+    let result = RegexLexer::new(&BASE_MACHINE, "root", "a #b").collect::<Vec<_>>();
+    assert_eq!(pairs(&result), vec![("a", TokenType::Name), (" ", TokenType::Text),
+                                   ("#", TokenType::Error), ("b", TokenType::Name)]);
+    let result = RegexLexer::new(&DERIVED_MACHINE, "root", "a #b").collect::<Vec<_>>();
+    assert_eq!(pairs(&result), vec![("a", TokenType::Name), (" ", TokenType::Whitespace),
+                                   ("#b", TokenType::Comment)]);
+}
+
 #[cfg(feature = "unstable")]
 mod benches {
     extern crate test;